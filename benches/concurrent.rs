@@ -0,0 +1,40 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! Throughput of creating temporaries from many threads at once, with and without the
+//! process-wide registry recording them, run with `cargo bench --bench concurrent`.
+extern crate mktemp;
+
+use mktemp::Temp;
+use std::thread;
+use std::time::Instant;
+
+const THREADS: u32 = 64;
+const PER_THREAD: u32 = 2_000;
+
+fn bench(name: &str) {
+    let start = Instant::now();
+    let threads: Vec<_> = (0..THREADS)
+        .map(|_| {
+            thread::spawn(|| {
+                for _ in 0..PER_THREAD {
+                    drop(Temp::new_path().release());
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let per_iter = start.elapsed() / (THREADS * PER_THREAD);
+
+    println!("{:<24} {:>8} ns/iter", name, per_iter.as_nanos());
+}
+
+fn main() {
+    bench("untracked");
+
+    mktemp::track_usage(true);
+    bench("tracked");
+    mktemp::track_usage(false);
+}
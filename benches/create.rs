@@ -0,0 +1,37 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! Rough throughput numbers for creating temporaries, run with `cargo bench`.
+extern crate mktemp;
+
+use mktemp::Builder;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 20_000;
+
+fn bench<F: FnMut()>(name: &str, mut f: F) {
+    // Warm up caches and the allocator before measuring.
+    for _ in 0..ITERATIONS / 10 {
+        f();
+    }
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let per_iter = start.elapsed() / ITERATIONS;
+
+    println!("{:<24} {:>8} ns/iter", name, per_iter.as_nanos());
+}
+
+fn main() {
+    let plain = Builder::new();
+    bench("path", || drop(plain.path().release()));
+
+    let mut named = Builder::new();
+    named.prefix("bench-").extension("tmp");
+    bench("path_with_ext", || drop(named.path().release()));
+
+    bench("file", || drop(plain.file().unwrap()));
+    bench("dir", || drop(plain.dir().unwrap()));
+}
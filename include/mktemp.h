@@ -0,0 +1,30 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+#ifndef MKTEMP_H
+#define MKTEMP_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct MktempTemp MktempTemp;
+
+/* Create a temporary file or directory. Returns NULL on failure. */
+MktempTemp *mktemp_new_file(void);
+MktempTemp *mktemp_new_dir(void);
+
+/* Borrow the path; valid until the handle is released or freed. */
+const char *mktemp_path(const MktempTemp *temp);
+
+/* Free the handle but keep the file or directory on disk. */
+void mktemp_release(MktempTemp *temp);
+
+/* Free the handle and remove the file or directory. */
+void mktemp_free(MktempTemp *temp);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MKTEMP_H */
@@ -0,0 +1,64 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! `Arbitrary` implementations, enabled with the `arbitrary` feature.
+//!
+//! Generated values are always valid: names only use portable characters and modes keep the
+//! owner able to read, write and clean up the temporary.
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use {Builder, Kind};
+
+const NAME_CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-_";
+
+fn name(u: &mut Unstructured) -> Result<String> {
+    let len = u.int_in_range(1..=8)?;
+    let mut name = String::with_capacity(len);
+    for _ in 0..len {
+        name.push(*u.choose(NAME_CHARS)? as char);
+    }
+    Ok(name)
+}
+
+impl<'a> Arbitrary<'a> for Kind {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(*u.choose(&[Kind::File, Kind::Dir, Kind::Path])?)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Builder {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut builder = Builder::new();
+
+        if u.arbitrary()? {
+            builder.prefix(&name(u)?);
+        }
+        if u.arbitrary()? {
+            builder.extension(&name(u)?);
+        }
+        if u.arbitrary()? {
+            builder.mode(0o700 | u.int_in_range(0..=0o77)?);
+        }
+
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arbitrary_configurations_build() {
+        let data: Vec<u8> = (0..4096u32).map(|i| (i * 31 % 251) as u8).collect();
+        let mut u = Unstructured::new(&data);
+
+        for _ in 0..32 {
+            let kind = Kind::arbitrary(&mut u).unwrap();
+            let builder = Builder::arbitrary(&mut u).unwrap();
+            let temp = builder.build(kind).unwrap();
+
+            assert_eq!(temp.is_present(), kind != Kind::Path);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use log;
+use path_error::annotate;
+use {Kind, Temp};
+
+impl Temp {
+    /// Archive the temporary as `<file name>.tar.gz` in `dir`, creating `dir` if needed, and
+    /// return the path of the archive. A directory is archived with its contents, under its own
+    /// name, without following symlinks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    ///
+    /// let artifacts = Temp::new_dir().unwrap();
+    /// let scratch = Temp::new_dir().unwrap();
+    /// fs::write(scratch.join("output.log"), "done").unwrap();
+    ///
+    /// let archive = scratch.archive_into(&artifacts).unwrap();
+    /// assert!(archive.starts_with(&artifacts));
+    /// assert!(archive.to_str().unwrap().ends_with(".tar.gz"));
+    /// ```
+    pub fn archive_into<P: AsRef<Path>>(&self, dir: P) -> io::Result<PathBuf> {
+        archive(&self.path, self.kind, dir.as_ref())
+    }
+
+    /// Archive the temporary into `dir`, like [`archive_into`](#method.archive_into), just
+    /// before it is removed. A failure to archive is reported through the
+    /// [`set_log_hook`](fn.set_log_hook.html) hook and doesn't stop the removal.
+    pub(crate) fn archive_before_removal(&self, dir: PathBuf) {
+        let kind = self.kind;
+        self.on_cleanup(move |path| {
+            if let Err(e) = archive(path, kind, &dir) {
+                log::warn(format_args!(
+                    "failed to archive {} into {}: {}",
+                    path.display(),
+                    dir.display(),
+                    e
+                ));
+            }
+        });
+    }
+}
+
+fn archive(path: &Path, kind: Kind, dir: &Path) -> io::Result<PathBuf> {
+    let name = match path.file_name() {
+        Some(name) => name,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} has no file name", path.display()),
+            ))
+        }
+    };
+    let is_dir = match kind {
+        Kind::File => false,
+        Kind::Dir => true,
+        Kind::Path => annotate(fs::symlink_metadata(path), "archive", path)?.is_dir(),
+    };
+    let mut archive_name = name.to_os_string();
+    archive_name.push(".tar.gz");
+    let target = dir.join(archive_name);
+
+    annotate(fs::create_dir_all(dir), "create", dir)?;
+    let file = annotate(File::create(&target), "create", &target)?;
+    let mut tar = ::tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    tar.follow_symlinks(false);
+    let appended = if is_dir {
+        tar.append_dir_all(name, path)
+    } else {
+        tar.append_path_with_name(path, name)
+    };
+    annotate(appended, "archive", path)?;
+    let encoder = annotate(tar.into_inner(), "write", &target)?;
+    annotate(encoder.finish(), "write", &target)?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use Builder;
+
+    fn entries(archive: &Path) -> Vec<PathBuf> {
+        let file = File::open(archive).unwrap();
+        let mut tar = ::tar::Archive::new(GzDecoder::new(file));
+        let mut paths: Vec<PathBuf> = tar
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().into_owned())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn dir_is_archived_before_removal() {
+        let artifacts = Temp::new_dir().unwrap();
+        let scratch = Builder::new()
+            .archive_before_removal(artifacts.join("archives"))
+            .dir()
+            .unwrap();
+        fs::write(scratch.join("output.log"), "done").unwrap();
+        let name = PathBuf::from(scratch.name());
+        let path = scratch.to_path_buf();
+        drop(scratch);
+
+        assert!(!path.exists());
+        let mut archive_name = name.clone().into_os_string();
+        archive_name.push(".tar.gz");
+        let archive = artifacts.join("archives").join(archive_name);
+        assert_eq!(entries(&archive), [name.clone(), name.join("output.log")]);
+    }
+
+    #[test]
+    fn cancellable_cleanup_archives_the_contents() {
+        let artifacts = Temp::new_dir().unwrap();
+        let scratch = Builder::new()
+            .archive_before_removal(&artifacts)
+            .dir()
+            .unwrap();
+        fs::write(scratch.join("output.log"), "done").unwrap();
+        let name = PathBuf::from(scratch.name());
+
+        scratch
+            .close_in_background_with(&::CancelToken::new())
+            .wait()
+            .unwrap();
+        let mut archive_name = name.clone().into_os_string();
+        archive_name.push(".tar.gz");
+        let archive = artifacts.join(archive_name);
+        assert_eq!(entries(&archive), [name.clone(), name.join("output.log")]);
+    }
+
+    #[test]
+    fn file_is_archived_under_its_name() {
+        let artifacts = Temp::new_dir().unwrap();
+        let temp_file = Temp::new_file().unwrap();
+        let archive = temp_file.archive_into(&artifacts).unwrap();
+        assert_eq!(entries(&archive), [PathBuf::from(temp_file.name())]);
+    }
+}
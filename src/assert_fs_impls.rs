@@ -0,0 +1,104 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! `assert_fs` extension trait implementations, enabled with the `assert_fs` feature.
+//!
+//! Each one behaves like it does for `assert_fs::fixture::ChildPath`, so a `Temp` can be used
+//! wherever an `assert_fs` fixture is expected.
+use assert_fs_crate::assert::{IntoPathPredicate, PathAssert};
+use assert_fs_crate::fixture::{
+    ChildPath, FileTouch, FileWriteBin, FileWriteFile, FileWriteStr, FixtureError, PathChild,
+    PathCopy, PathCreateDir,
+};
+use predicates_core::Predicate;
+use std::path::Path;
+
+use Temp;
+
+impl Temp {
+    fn child_path(&self) -> ChildPath {
+        ChildPath::new(self.path())
+    }
+}
+
+impl PathChild for Temp {
+    fn child<P>(&self, path: P) -> ChildPath
+    where
+        P: AsRef<Path>,
+    {
+        ChildPath::new(self.join(path))
+    }
+}
+
+impl PathAssert for Temp {
+    #[track_caller]
+    fn assert<I, P>(&self, pred: I) -> &Self
+    where
+        I: IntoPathPredicate<P>,
+        P: Predicate<Path>,
+    {
+        self.child_path().assert(pred);
+        self
+    }
+}
+
+impl PathCreateDir for Temp {
+    fn create_dir_all(&self) -> Result<(), FixtureError> {
+        self.child_path().create_dir_all()
+    }
+}
+
+impl FileTouch for Temp {
+    fn touch(&self) -> Result<(), FixtureError> {
+        self.child_path().touch()
+    }
+}
+
+impl FileWriteBin for Temp {
+    fn write_binary(&self, data: &[u8]) -> Result<(), FixtureError> {
+        self.child_path().write_binary(data)
+    }
+}
+
+impl FileWriteStr for Temp {
+    fn write_str(&self, data: &str) -> Result<(), FixtureError> {
+        self.child_path().write_str(data)
+    }
+}
+
+impl FileWriteFile for Temp {
+    fn write_file(&self, data: &Path) -> Result<(), FixtureError> {
+        self.child_path().write_file(data)
+    }
+}
+
+impl PathCopy for Temp {
+    fn copy_from<P, S>(&self, source: P, patterns: &[S]) -> Result<(), FixtureError>
+    where
+        P: AsRef<Path>,
+        S: AsRef<str>,
+    {
+        self.child_path().copy_from(source, patterns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_dir_works_with_assert_fs() {
+        let dir = Temp::new_dir().unwrap();
+        let child = dir.child("nested/file.txt");
+        child.write_str("contents").unwrap();
+
+        child.assert("contents");
+    }
+
+    #[test]
+    fn temp_file_works_with_assert_fs() {
+        let file = Temp::new_file().unwrap();
+        file.write_binary(b"data").unwrap();
+        file.assert("data");
+    }
+}
@@ -0,0 +1,209 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use backend::OsFs;
+use {Builder, Temp};
+
+/// Replace the contents of `path` atomically: readers see either the old or the new contents,
+/// never a partially written file, even if the process crashes part way through.
+///
+/// The contents are written to a temporary file in the same directory as `path`, so the final
+/// rename never crosses a file system, flushed to disk, and then renamed over `path`. An
+/// existing file's permissions are kept.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::{atomic_write, Temp};
+/// use std::fs;
+///
+/// let dir = Temp::new_dir().unwrap();
+/// let config = dir.join("app.conf");
+/// atomic_write(&config, "verbose = true\n").unwrap();
+/// assert_eq!(fs::read_to_string(&config).unwrap(), "verbose = true\n");
+/// ```
+pub fn atomic_write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> io::Result<()> {
+    let mut writer = AtomicWriter::new(path)?;
+    writer.write_all(contents.as_ref())?;
+    writer.commit()
+}
+
+/// Writes into a hidden temporary file which only replaces its destination when
+/// [`commit`](#method.commit) is called. Dropping the writer without committing removes the
+/// temporary and leaves the destination untouched.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::{AtomicWriter, Temp};
+/// use std::fs;
+/// use std::io::Write;
+///
+/// let dir = Temp::new_dir().unwrap();
+/// let path = dir.join("data.csv");
+///
+/// let mut writer = AtomicWriter::new(&path).unwrap();
+/// writeln!(writer, "id,name").unwrap();
+/// assert!(!path.exists());
+///
+/// writer.commit().unwrap();
+/// assert_eq!(fs::read_to_string(&path).unwrap(), "id,name\n");
+/// ```
+#[derive(Debug)]
+pub struct AtomicWriter {
+    // Declared before `temp` so the file is closed before it is removed.
+    file: fs::File,
+    temp: Temp,
+    destination: PathBuf,
+}
+
+impl AtomicWriter {
+    /// Start writing a replacement for `destination`.
+    pub fn new<P: AsRef<Path>>(destination: P) -> io::Result<Self> {
+        let destination = destination.as_ref().to_path_buf();
+
+        let prefix = match destination.file_name() {
+            Some(name) => format!(".{}.", name.to_string_lossy()),
+            None => ".".to_string(),
+        };
+        // Like `fs::write`, new files get the default permissions after the umask is applied.
+        // An explicit backend opts out of the crate-wide keep, dry-run and crash artifact
+        // settings, so an abandoned staging file never stays next to the destination.
+        let temp = Builder::new()
+            .in_dir(parent_dir(&destination)?)
+            .prefix(&prefix)
+            .fs(Arc::new(OsFs))
+            .mode(0o666)
+            .file()?;
+        let file = fs::OpenOptions::new().write(true).open(&temp)?;
+
+        Ok(AtomicWriter {
+            file,
+            temp,
+            destination,
+        })
+    }
+
+    /// The path that will be replaced on commit.
+    pub fn destination(&self) -> &Path {
+        &self.destination
+    }
+
+    /// Flush everything written to disk and rename it over the destination. An existing
+    /// destination's permissions are kept.
+    pub fn commit(self) -> io::Result<()> {
+        let AtomicWriter {
+            mut file,
+            temp,
+            destination,
+        } = self;
+
+        file.flush()?;
+        if let Ok(metadata) = fs::metadata(&destination) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        drop(file);
+
+        fs::rename(&temp, &destination)?;
+        temp.release();
+
+        sync_dir(parent_dir(&destination)?)
+    }
+}
+
+impl Write for AtomicWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+pub(crate) fn parent_dir(path: &Path) -> io::Result<&Path> {
+    match path.parent() {
+        Some(dir) if dir.as_os_str().is_empty() => Ok(Path::new(".")),
+        Some(dir) => Ok(dir),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} has no parent directory", path.display()),
+        )),
+    }
+}
+
+/// Make a rename in `dir` durable.
+#[cfg(unix)]
+pub(crate) fn sync_dir(dir: &Path) -> io::Result<()> {
+    fs::File::open(dir)?.sync_all()
+}
+
+/// Make a rename in `dir` durable.
+#[cfg(not(unix))]
+pub(crate) fn sync_dir(_dir: &Path) -> io::Result<()> {
+    // Directories can't be opened like this elsewhere, and renames are journaled on NTFS.
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_existing_file() {
+        let dir = Temp::new_dir().unwrap();
+        let path = dir.join("file");
+        fs::write(&path, b"old contents").unwrap();
+
+        atomic_write(&path, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn keeps_existing_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = Temp::new_dir().unwrap();
+        let path = dir.join("script");
+        fs::write(&path, b"").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o751)).unwrap();
+
+        atomic_write(&path, b"#!/bin/sh\n").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(0o751, mode & 0o777);
+    }
+
+    #[test]
+    fn dropped_writer_leaves_destination_untouched() {
+        let dir = Temp::new_dir().unwrap();
+        let path = dir.join("file");
+        fs::write(&path, b"old").unwrap();
+
+        let mut writer = AtomicWriter::new(&path).unwrap();
+        writer.write_all(b"new").unwrap();
+        let staging = writer.temp.name().to_str().unwrap().to_owned();
+        assert!(staging.starts_with(".file."), "{}", staging);
+        drop(writer);
+
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn missing_directory_fails_cleanly() {
+        let dir = Temp::new_dir().unwrap();
+        match atomic_write(dir.join("missing").join("file"), b"") {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            _ => panic!(),
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,117 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! JSON Lines records of the temporaries created and removed, for auditing.
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+use usage::size;
+use {Kind, Temp};
+
+type Sink = Box<dyn Write + Send>;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+
+/// Write a JSON record of every temporary created and removed to `sink`, one per line, or stop
+/// with `None`. Each record has the `event` (`created`, `deleted` or `failed`), the `time` in
+/// seconds since the Unix epoch, the `path`, the `kind` (`file`, `dir` or `path`) and the
+/// `label`, if any. Removals also have the `size` in bytes, for directories including their
+/// contents, and the `duration` in seconds; failures have the `error`.
+///
+/// Failing to write a record doesn't affect the temporary.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::Temp;
+/// use std::fs::OpenOptions;
+///
+/// let log = Temp::new_file().unwrap();
+/// let file = OpenOptions::new().append(true).open(&log).unwrap();
+/// mktemp::set_audit_sink(Some(Box::new(file)));
+///
+/// drop(Temp::new_file().unwrap());
+/// mktemp::set_audit_sink(None);
+///
+/// let records = std::fs::read_to_string(&log).unwrap();
+/// assert!(records.lines().next().unwrap().contains(r#""event":"created""#));
+/// assert!(records.lines().nth(1).unwrap().contains(r#""event":"deleted""#));
+/// ```
+pub fn set_audit_sink(sink: Option<Box<dyn Write + Send>>) {
+    let mut current = SINK.lock().unwrap_or_else(|e| e.into_inner());
+    ENABLED.store(sink.is_some(), Ordering::Relaxed);
+    *current = sink;
+}
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn emit(event: &str, temp: &Temp, mut record: Value) {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let kind = match temp.kind {
+        Kind::File => "file",
+        Kind::Dir => "dir",
+        Kind::Path => "path",
+    };
+    record["event"] = json!(event);
+    record["time"] = json!(time);
+    record["path"] = json!(temp.path.to_string_lossy());
+    record["kind"] = json!(kind);
+    record["label"] = json!(temp.label());
+
+    let mut sink = SINK.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(ref mut sink) = *sink {
+        let _result = writeln!(sink, "{}", record).and_then(|_| sink.flush());
+    }
+}
+
+/// Record that `temp` was created.
+pub(crate) fn created(temp: &Temp) {
+    if enabled() {
+        emit("created", temp, json!({}));
+    }
+}
+
+/// Measures the removal of a temporary, to record its outcome.
+pub(crate) struct Removal {
+    started: Instant,
+    size: Option<u64>,
+}
+
+impl Removal {
+    pub(crate) fn start(path: &Path) -> Option<Removal> {
+        if !enabled() {
+            return None;
+        }
+        Some(Removal {
+            size: size(path),
+            started: Instant::now(),
+        })
+    }
+
+    pub(crate) fn finish(self, temp: &Temp, result: &io::Result<()>) {
+        let duration = self.started.elapsed().as_secs_f64();
+        match *result {
+            Ok(()) => emit(
+                "deleted",
+                temp,
+                json!({ "size": self.size, "duration": duration }),
+            ),
+            Err(ref e) => emit(
+                "failed",
+                temp,
+                json!({ "size": self.size, "duration": duration, "error": e.to_string() }),
+            ),
+        }
+    }
+}
@@ -0,0 +1,653 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! The file system operations a [`Temp`](../struct.Temp.html) performs over its lifetime.
+//!
+//! By default the real file system is used ([`OsFs`](struct.OsFs.html)). Tests can supply their
+//! own [`TempFs`](trait.TempFs.html) through a [`Builder`](../struct.Builder.html) to observe
+//! those operations or to simulate failures such as a full disk, or use
+//! [`MemFs`](struct.MemFs.html) to avoid touching the disk at all.
+//!
+//! # Examples
+//!
+//! ```
+//! use mktemp::backend::TempFs;
+//! use mktemp::Builder;
+//! use std::io;
+//! use std::path::Path;
+//! use std::sync::Arc;
+//!
+//! struct DiskFull;
+//!
+//! impl TempFs for DiskFull {
+//!     fn create_file(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+//!         Err(io::Error::new(io::ErrorKind::Other, "no space left on device"))
+//!     }
+//!
+//!     fn create_dir(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+//!         Err(io::Error::new(io::ErrorKind::Other, "no space left on device"))
+//!     }
+//!
+//!     fn remove(&self, _path: &Path) -> io::Result<()> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! assert!(Builder::new().fs(Arc::new(DiskFull)).file().is_err());
+//! ```
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use path_error::{annotate, annotate2};
+#[cfg(any(windows, all(unix, feature = "fast_remove")))]
+use sys;
+
+/// File system operations used to create and clean up temporary files and directories.
+pub trait TempFs: Send + Sync {
+    /// Create a new, empty file with the permission bits `mode`. Must fail if `path` already
+    /// exists.
+    fn create_file(&self, path: &Path, mode: u32) -> io::Result<()>;
+
+    /// Create a new, empty directory with the permission bits `mode`. Must fail if `path`
+    /// already exists.
+    fn create_dir(&self, path: &Path, mode: u32) -> io::Result<()>;
+
+    /// Remove whatever exists at `path`, recursively for directories.
+    ///
+    /// Used for uninitialized temporaries, where the kind of resource isn't known.
+    fn remove(&self, path: &Path) -> io::Result<()>;
+
+    /// Remove the file at `path`, failing if it isn't a file.
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.remove(path)
+    }
+
+    /// Remove the directory at `path` and its contents, failing if it isn't a directory.
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        self.remove(path)
+    }
+
+    /// Rename `from` to `to`. Defaults to renaming on the real file system.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    /// Whether the temporaries are created on the real file system, so they can be opened,
+    /// linked or probed with `std::fs`. Operations that need that fail with `Unsupported` if
+    /// they aren't, and the default directory is used without checking it on disk.
+    fn is_on_disk(&self) -> bool {
+        true
+    }
+}
+
+/// Fail with `Unsupported` unless `fs` creates temporaries on disk.
+pub(crate) fn require_disk(fs: &dyn TempFs, operation: &str) -> io::Result<()> {
+    if fs.is_on_disk() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{} needs temporaries on disk", operation),
+        ))
+    }
+}
+
+/// The real file system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OsFs;
+
+impl TempFs for OsFs {
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn create_file(&self, path: &Path, mode: u32) -> io::Result<()> {
+        let mut builder = fs::OpenOptions::new();
+        builder.write(true).create_new(true);
+
+        #[cfg(unix)]
+        builder.mode(mode);
+
+        annotate(builder.open(path), "create file", path)?;
+        Ok(())
+    }
+
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn create_dir(&self, path: &Path, mode: u32) -> io::Result<()> {
+        let mut builder = fs::DirBuilder::new();
+
+        #[cfg(unix)]
+        builder.mode(mode);
+
+        annotate(builder.create(path), "create directory", path)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        if annotate(fs::symlink_metadata(path), "read metadata of", path)?.is_dir() {
+            self.remove_dir_all(path)
+        } else {
+            self.remove_file(path)
+        }
+    }
+
+    #[cfg_attr(windows, allow(clippy::permissions_set_readonly_false))]
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        // Removed names linger while "delete pending" otherwise, so recreating them fails.
+        #[cfg(windows)]
+        match sys::posix_delete(path) {
+            Err(ref e) if sys::is_unsupported(e) => {}
+            result => return annotate(result, "remove file", path),
+        }
+
+        let result = match fs::remove_file(path) {
+            // Read-only files, e.g. sealed ones, can't be removed on Windows.
+            #[cfg(windows)]
+            Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                let mut permissions = fs::metadata(path)?.permissions();
+                permissions.set_readonly(false);
+                fs::set_permissions(path, permissions).and_then(|()| fs::remove_file(path))
+            }
+            result => result,
+        };
+        annotate(result, "remove file", path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        #[cfg(all(unix, feature = "fast_remove"))]
+        let result = sys::remove_dir_all(path);
+        #[cfg(windows)]
+        let result = match sys::posix_remove_dir_all(path) {
+            Err(ref e) if sys::is_unsupported(e) => fs::remove_dir_all(path),
+            result => result,
+        };
+        #[cfg(not(any(windows, all(unix, feature = "fast_remove"))))]
+        let result = fs::remove_dir_all(path);
+
+        annotate(result, "remove directory", path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        annotate2(fs::rename(from, to), "rename", from, to)
+    }
+}
+
+/// The real file system, except that temporaries are moved to the trash or recycle bin instead
+/// of being deleted, so users can recover them. Requires the `trash` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mktemp::backend::TrashFs;
+/// use mktemp::Builder;
+/// use std::sync::Arc;
+///
+/// let export = Builder::new()
+///     .fs(Arc::new(TrashFs))
+///     .extension("csv")
+///     .file()
+///     .unwrap();
+/// // `export` is moved to the trash here.
+/// ```
+#[cfg(feature = "trash")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TrashFs;
+
+#[cfg(feature = "trash")]
+impl TempFs for TrashFs {
+    fn create_file(&self, path: &Path, mode: u32) -> io::Result<()> {
+        OsFs.create_file(path, mode)
+    }
+
+    fn create_dir(&self, path: &Path, mode: u32) -> io::Result<()> {
+        OsFs.create_dir(path, mode)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        // Keep reporting missing paths as `NotFound`, which cleanup ignores.
+        fs::symlink_metadata(path)?;
+        ::trash::delete(path).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+}
+
+/// Creates temporaries like the file system it wraps, but only records removals instead of
+/// performing them, to audit what an application would clean up. See also the crate-wide
+/// [`set_dry_run`](../fn.set_dry_run.html).
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::backend::DryRunFs;
+/// use mktemp::Builder;
+/// use std::sync::Arc;
+///
+/// let fs = Arc::new(DryRunFs::new());
+/// let path = Builder::new().fs(fs.clone()).file().unwrap().to_path_buf();
+///
+/// assert_eq!(fs.report(), vec![path.clone()]);
+/// assert!(path.exists());
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+pub struct DryRunFs {
+    inner: Option<Arc<dyn TempFs>>,
+    removed: Mutex<Vec<PathBuf>>,
+}
+
+impl DryRunFs {
+    /// Create temporaries on the real file system.
+    pub const fn new() -> Self {
+        DryRunFs {
+            inner: None,
+            removed: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Create temporaries with `inner`.
+    pub fn wrapping(inner: Arc<dyn TempFs>) -> Self {
+        DryRunFs {
+            inner: Some(inner),
+            removed: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The paths that would have been removed, in order.
+    pub fn report(&self) -> Vec<PathBuf> {
+        self.removed().clone()
+    }
+
+    /// The paths that would have been removed, in order, forgetting them.
+    pub fn take_report(&self) -> Vec<PathBuf> {
+        ::std::mem::take(&mut *self.removed())
+    }
+
+    fn inner(&self) -> &dyn TempFs {
+        match self.inner {
+            Some(ref fs) => &**fs,
+            None => &OsFs,
+        }
+    }
+
+    fn removed(&self) -> MutexGuard<'_, Vec<PathBuf>> {
+        self.removed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record(&self, path: &Path) -> io::Result<()> {
+        self.removed().push(path.to_path_buf());
+        Ok(())
+    }
+}
+
+impl Default for DryRunFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for DryRunFs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DryRunFs")
+            .field("removed", &*self.removed())
+            .finish()
+    }
+}
+
+impl TempFs for DryRunFs {
+    fn create_file(&self, path: &Path, mode: u32) -> io::Result<()> {
+        self.inner().create_file(path, mode)
+    }
+
+    fn create_dir(&self, path: &Path, mode: u32) -> io::Result<()> {
+        self.inner().create_dir(path, mode)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.record(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.record(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        self.record(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.inner().rename(from, to)
+    }
+
+    fn is_on_disk(&self) -> bool {
+        self.inner().is_on_disk()
+    }
+}
+
+/// An in-memory file system, for running code that uses temporaries without disk I/O.
+///
+/// Parent directories that were never created in memory, such as the system temp directory,
+/// are assumed to exist, and the default directory is never probed or created on disk.
+/// Operations that need a real file fail with `Unsupported`: `Builder::open_file`,
+/// `sparse_file` and `executable_file`, and `Temp::alias` and `swap_with`. `Temp` methods that
+/// work on the file at its path, e.g. `sync` or `set_mode`, only work on disk too.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::backend::MemFs;
+/// use mktemp::Builder;
+/// use std::sync::Arc;
+///
+/// let fs = Arc::new(MemFs::new());
+/// let path = {
+///     let dir = Builder::new().fs(fs.clone()).dir().unwrap();
+///     let file = dir.join("data.txt");
+///     fs.write(&file, b"hello").unwrap();
+///
+///     assert!(!dir.exists());
+///     assert_eq!(fs.read(&file).unwrap(), b"hello");
+///     file
+/// };
+/// assert!(!fs.exists(&path));
+/// ```
+#[derive(Debug, Default)]
+pub struct MemFs {
+    entries: Mutex<BTreeMap<PathBuf, Entry>>,
+}
+
+#[derive(Debug)]
+enum Entry {
+    File(Vec<u8>),
+    Dir,
+}
+
+impl MemFs {
+    /// Create an empty in-memory file system.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a file or directory exists at `path`.
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.entries().contains_key(path.as_ref())
+    }
+
+    /// Whether a file exists at `path`.
+    pub fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
+        matches!(self.entries().get(path.as_ref()), Some(Entry::File(_)))
+    }
+
+    /// Whether a directory exists at `path`.
+    pub fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+        matches!(self.entries().get(path.as_ref()), Some(Entry::Dir))
+    }
+
+    /// Create or replace the file at `path` with `contents`.
+    pub fn write<P: AsRef<Path>>(&self, path: P, contents: &[u8]) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut entries = self.entries();
+        Self::check_parent(&entries, path)?;
+
+        match entries.get_mut(path) {
+            Some(Entry::Dir) => Err(is_a_directory(path)),
+            Some(Entry::File(data)) => {
+                *data = contents.to_vec();
+                Ok(())
+            }
+            None => {
+                entries.insert(path.to_path_buf(), Entry::File(contents.to_vec()));
+                Ok(())
+            }
+        }
+    }
+
+    /// Read the contents of the file at `path`.
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<u8>> {
+        let path = path.as_ref();
+        match self.entries().get(path) {
+            Some(Entry::File(data)) => Ok(data.clone()),
+            Some(Entry::Dir) => Err(is_a_directory(path)),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn entries(&self) -> MutexGuard<'_, BTreeMap<PathBuf, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn check_parent(entries: &BTreeMap<PathBuf, Entry>, path: &Path) -> io::Result<()> {
+        match path.parent().and_then(|parent| entries.get(parent)) {
+            Some(Entry::File(_)) => Err(not_found(path)),
+            _ => Ok(()),
+        }
+    }
+
+    fn create(&self, path: &Path, entry: Entry) -> io::Result<()> {
+        let mut entries = self.entries();
+        Self::check_parent(&entries, path)?;
+
+        if entries.contains_key(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            ));
+        }
+
+        entries.insert(path.to_path_buf(), entry);
+        Ok(())
+    }
+}
+
+impl TempFs for MemFs {
+    fn create_file(&self, path: &Path, _mode: u32) -> io::Result<()> {
+        self.create(path, Entry::File(Vec::new()))
+    }
+
+    fn create_dir(&self, path: &Path, _mode: u32) -> io::Result<()> {
+        self.create(path, Entry::Dir)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.entries().retain(|entry, _| !entry.starts_with(path));
+        Ok(())
+    }
+
+    fn is_on_disk(&self) -> bool {
+        false
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut entries = self.entries();
+        Self::check_parent(&entries, to)?;
+
+        let moved: Vec<PathBuf> = entries
+            .keys()
+            .filter(|entry| entry.starts_with(from))
+            .cloned()
+            .collect();
+        if moved.is_empty() {
+            return Err(not_found(from));
+        }
+
+        for old in moved {
+            let entry = entries.remove(&old).expect("entry was just listed");
+            let new = to.join(old.strip_prefix(from).expect("entry is under from"));
+            entries.insert(new, entry);
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        match self.entries().get(path) {
+            Some(Entry::Dir) => return Err(is_a_directory(path)),
+            None => return Err(not_found(path)),
+            Some(Entry::File(_)) => (),
+        }
+        self.remove(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        match self.entries().get(path) {
+            Some(Entry::File(_)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("{} is not a directory", path.display()),
+                ))
+            }
+            None => return Err(not_found(path)),
+            Some(Entry::Dir) => (),
+        }
+        self.remove(path)
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} not found", path.display()),
+    )
+}
+
+fn is_a_directory(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        format!("{} is a directory", path.display()),
+    )
+}
+
+/// A backend for tests which creates nothing and records what is removed.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct Recorder {
+    pub(crate) removed: Mutex<Vec<PathBuf>>,
+    /// Fail creating directories with `PermissionDenied`.
+    pub(crate) deny_dirs: bool,
+}
+
+#[cfg(test)]
+impl TempFs for Recorder {
+    fn create_file(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn create_dir(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+        if self.deny_dirs {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "denied"));
+        }
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.removed.lock().unwrap().push(path.to_path_buf());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Builder;
+
+    #[test]
+    fn custom_fs_is_used_for_create_and_remove() {
+        let fs = Arc::new(Recorder::default());
+
+        let path = {
+            let temp = Builder::new().fs(fs.clone()).file().unwrap();
+            assert!(!temp.exists());
+            temp.to_path_buf()
+        };
+
+        assert_eq!(*fs.removed.lock().unwrap(), vec![path]);
+    }
+
+    #[test]
+    fn dry_run_fs_records_instead_of_removing() {
+        let mem = Arc::new(MemFs::new());
+        let fs = Arc::new(DryRunFs::wrapping(mem.clone()));
+
+        let (dir, file) = {
+            let dir = Builder::new().fs(fs.clone()).dir().unwrap();
+            let file = Builder::new().fs(fs.clone()).in_dir(&dir).file().unwrap();
+            (dir.to_path_buf(), file.to_path_buf())
+        };
+
+        assert_eq!(fs.report(), vec![file.clone(), dir.clone()]);
+        assert!(mem.is_dir(dir));
+        assert!(mem.is_file(file));
+    }
+
+    #[test]
+    fn mem_fs_removes_dir_contents() {
+        let fs = Arc::new(MemFs::new());
+
+        let (dir_path, file_path) = {
+            let dir = Builder::new().fs(fs.clone()).dir().unwrap();
+            let file = Builder::new().fs(fs.clone()).in_dir(&dir).file().unwrap();
+            fs.write(&file, b"data").unwrap();
+
+            assert!(fs.is_dir(&dir));
+            assert!(fs.is_file(&file));
+            assert!(!dir.exists());
+
+            let file_path = file.release();
+            (dir.to_path_buf(), file_path)
+        };
+
+        assert!(!fs.exists(dir_path));
+        assert!(!fs.exists(file_path));
+    }
+
+    #[test]
+    fn mem_fs_renames_dir_contents() {
+        let fs = MemFs::new();
+        fs.create_dir(Path::new("/mem/a"), 0o700).unwrap();
+        fs.write("/mem/a/file", b"data").unwrap();
+
+        fs.rename(Path::new("/mem/a"), Path::new("/mem/b")).unwrap();
+        assert!(!fs.exists("/mem/a"));
+        assert!(fs.is_dir("/mem/b"));
+        assert_eq!(fs.read("/mem/b/file").unwrap(), b"data");
+    }
+
+    #[test]
+    fn mem_fs_rejects_disk_operations() {
+        let mut builder = Builder::new();
+        builder.fs(Arc::new(MemFs::new()));
+        let unsupported = |e: io::Error| assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+
+        unsupported(builder.open_file().unwrap_err());
+        unsupported(builder.sparse_file(1).unwrap_err());
+        unsupported(builder.file().unwrap().alias().unwrap_err());
+    }
+
+    #[test]
+    fn mem_fs_rejects_existing_paths() {
+        let fs = MemFs::new();
+        let path = Path::new("/mem/file");
+
+        fs.create_file(path, 0o600).unwrap();
+        match fs.create_dir(path, 0o700) {
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+            _ => panic!(),
+        }
+        match fs.write(path.join("child"), b"") {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn custom_fs_errors_are_returned() {
+        let fs = Arc::new(Recorder {
+            deny_dirs: true,
+            ..Recorder::default()
+        });
+
+        match Builder::new().fs(fs.clone()).dir() {
+            Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied => (),
+            _ => panic!(),
+        }
+        assert!(fs.removed.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,989 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::panic::Location;
+use std::path::{self, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use backend::{require_disk, OsFs, TempFs};
+use config;
+use error::{NoExecError, ReadOnlyError};
+use limit;
+use log;
+#[cfg(any(unix, windows))]
+use path_error::raw_os_error;
+use registry;
+use root;
+use sys;
+use {create_path_in, Kind, Temp, TempFile, MIN_ENTROPY_BITS};
+
+/// Configure how a temporary file or directory is created.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::Builder;
+///
+/// let parent = Builder::new().dir().unwrap();
+/// let temp_file = Builder::new()
+///     .in_dir(&parent)
+///     .prefix("report-")
+///     .extension("csv")
+///     .file()
+///     .unwrap();
+/// assert!(temp_file.starts_with(&parent));
+/// ```
+#[derive(Clone)]
+pub struct Builder {
+    dir: Option<PathBuf>,
+    stripes: Option<Arc<Stripes>>,
+    fs: Option<Arc<dyn TempFs>>,
+    prefix: String,
+    extension: Option<String>,
+    entropy_bits: Option<u32>,
+    expect_empty: bool,
+    label: Option<Arc<str>>,
+    mode: Option<u32>,
+    strict: Option<bool>,
+    sync_on_release: bool,
+    delete_on_reboot: bool,
+    retry_when_full: bool,
+    canonicalize: bool,
+    cleanup_deadline: Option<Duration>,
+    crash_artifacts: Option<PathBuf>,
+    not_content_indexed: bool,
+    #[cfg(feature = "archive")]
+    archive_dir: Option<PathBuf>,
+    inheritable: bool,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder {
+            dir: None,
+            stripes: None,
+            fs: None,
+            prefix: config::default_prefix(),
+            extension: None,
+            entropy_bits: None,
+            expect_empty: false,
+            label: None,
+            mode: None,
+            strict: None,
+            sync_on_release: false,
+            delete_on_reboot: false,
+            retry_when_full: false,
+            canonicalize: false,
+            cleanup_deadline: None,
+            crash_artifacts: config::crash_artifacts(),
+            not_content_indexed: false,
+            #[cfg(feature = "archive")]
+            archive_dir: None,
+            inheritable: false,
+        }
+    }
+}
+
+impl Builder {
+    /// Create a builder with the default settings, see [`configure`](fn.configure.html).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the temporary in an existing directory instead of the system temp directory.
+    pub fn in_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Create temporaries in `dirs` in turn, round-robin, instead of the system temp directory,
+    /// e.g. one directory on each of several disks so a build writing many large intermediates
+    /// uses all of them at once. The turn is shared by clones of the builder. A directory given
+    /// with [`in_dir`](#method.in_dir) takes precedence.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dirs` is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::{Builder, Temp};
+    ///
+    /// let (disk_a, disk_b) = (Temp::new_dir().unwrap(), Temp::new_dir().unwrap());
+    /// let outputs = Builder::new()
+    ///     .stripe_across(vec![disk_a.to_path_buf(), disk_b.to_path_buf()])
+    ///     .files(4)
+    ///     .unwrap();
+    /// assert!(outputs[0].starts_with(&disk_a) && outputs[1].starts_with(&disk_b));
+    /// assert!(outputs[2].starts_with(&disk_a) && outputs[3].starts_with(&disk_b));
+    /// ```
+    pub fn stripe_across<I>(&mut self, dirs: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: Into<PathBuf>,
+    {
+        let dirs: Vec<PathBuf> = dirs.into_iter().map(Into::into).collect();
+        assert!(!dirs.is_empty(), "no directories to stripe across");
+        self.stripes = Some(Arc::new(Stripes {
+            dirs,
+            next: AtomicUsize::new(0),
+        }));
+        self
+    }
+
+    /// Use a different file system implementation, see [`backend`](backend/index.html).
+    pub fn fs(&mut self, fs: Arc<dyn TempFs>) -> &mut Self {
+        self.fs = Some(fs);
+        self
+    }
+
+    /// Start the generated name with `prefix`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `prefix` contains a path separator.
+    pub fn prefix(&mut self, prefix: &str) -> &mut Self {
+        assert_no_separator(prefix);
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    /// Give the generated name an extension, e.g. `"txt"`. It is appended after the random part
+    /// of the name, even if the prefix contains a dot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `extension` contains a path separator.
+    pub fn extension(&mut self, extension: &str) -> &mut Self {
+        assert_no_separator(extension);
+        self.extension = Some(extension.to_owned());
+        self
+    }
+
+    /// Make the random part of the generated name just long enough for `bits` bits of entropy,
+    /// from lowercase letters and digits at 5 bits per character, instead of a 32 character
+    /// UUID with 122 bits. 64 bits, 13 characters, is plenty to avoid collisions for names
+    /// that only have to be unique while they exist, and keeps paths short, e.g. for Unix
+    /// socket paths.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` is below 64, which would make collisions and guessing likely enough to
+    /// matter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Builder;
+    ///
+    /// let socket = Builder::new().prefix("s").min_entropy_bits(64).path();
+    /// assert_eq!(socket.name().len(), 1 + 13);
+    /// ```
+    pub fn min_entropy_bits(&mut self, bits: u32) -> &mut Self {
+        assert!(
+            bits >= MIN_ENTROPY_BITS,
+            "{} bits of entropy is below the floor of {}",
+            bits,
+            MIN_ENTROPY_BITS
+        );
+        self.entropy_bits = Some(bits);
+        self
+    }
+
+    /// Label the temporary with its purpose, e.g. `"upload-staging"`, which is included in the
+    /// crate's warnings, in [`usage_by_label`](fn.usage_by_label.html) and in
+    /// [leak reports](struct.Leak.html#method.label), so temp usage can be told apart by more
+    /// than its path.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Builder;
+    ///
+    /// let staging = Builder::new().label("upload-staging").file().unwrap();
+    /// assert_eq!(staging.label(), Some("upload-staging"));
+    /// ```
+    pub fn label(&mut self, label: &str) -> &mut Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Set the permission bits used on creation, `0o600` for files and `0o700` for directories
+    /// by default. Only used on unix.
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Panic in debug builds if the temporary can't be removed when it is dropped, overriding
+    /// the crate-wide [`set_strict`](fn.set_strict.html) setting.
+    pub fn strict(&mut self, strict: bool) -> &mut Self {
+        self.strict = Some(strict);
+        self
+    }
+
+    /// Flush the temporary to disk with [`Temp::sync`](struct.Temp.html#method.sync) when it is
+    /// released, for temporaries that are kept and have to survive a crash.
+    pub fn sync_on_release(&mut self, sync: bool) -> &mut Self {
+        self.sync_on_release = sync;
+        self
+    }
+
+    /// On Windows, set `FILE_ATTRIBUTE_NOT_CONTENT_INDEXED` on the temporary, so Windows Search
+    /// doesn't index large scratch files, which costs time and briefly locks them, making
+    /// removal fail. Files created inside a directory inherit the attribute. Failing to set it
+    /// isn't an error. Has no effect on other platforms or with a custom [`fs`](#method.fs)
+    /// backend.
+    pub fn not_content_indexed(&mut self, not_indexed: bool) -> &mut Self {
+        self.not_content_indexed = not_indexed;
+        self
+    }
+
+    /// On Windows, if the temporary can't be removed when it is dropped, e.g. because a virus
+    /// scanner holds it open, schedule it to be deleted on the next reboot with
+    /// `MoveFileEx(MOVEFILE_DELAY_UNTIL_REBOOT)` instead of leaking it. This needs the rights to
+    /// write to `HKEY_LOCAL_MACHINE`, usually an elevated process, and has no effect on other
+    /// platforms or with a custom [`fs`](#method.fs) backend.
+    pub fn delete_on_reboot(&mut self, delete_on_reboot: bool) -> &mut Self {
+        self.delete_on_reboot = delete_on_reboot;
+        self
+    }
+
+    /// If the disk is full or read-only, retry creating the temporary in the next usable
+    /// candidate set with [`set_roots`](fn.set_roots.html), warning through the
+    /// [`set_log_hook`](fn.set_log_hook.html) hook, instead of failing. Has no effect when a
+    /// directory is given with [`in_dir`](#method.in_dir).
+    pub fn retry_when_full(&mut self, retry: bool) -> &mut Self {
+        self.retry_when_full = retry;
+        self
+    }
+
+    /// Stop waiting for the temporary to be removed, when it is dropped or
+    /// [`close`](struct.Temp.html#method.close)d, after `deadline`, and leave the rest of the
+    /// removal to a background thread, warning through the
+    /// [`set_log_hook`](fn.set_log_hook.html) hook; `close` returns a `TimedOut` error. The
+    /// temporary is [tracked for leaks](fn.leak_check.html) until the background removal
+    /// has finished. This keeps a large directory, or a hung
+    /// network file system, from stalling the code that drops it. The removal then happens on a
+    /// new thread, which costs a little time even when it is quick.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Builder;
+    /// use std::time::Duration;
+    ///
+    /// let cache = Builder::new()
+    ///     .cleanup_deadline(Duration::from_secs(2))
+    ///     .dir()
+    ///     .unwrap();
+    /// // Dropping `cache` blocks for at most two seconds.
+    /// ```
+    pub fn cleanup_deadline(&mut self, deadline: Duration) -> &mut Self {
+        self.cleanup_deadline = Some(deadline);
+        self
+    }
+
+    /// Move the temporary into `dir` instead of removing it if it is dropped while the thread is
+    /// panicking, so the evidence of a failed test or a crash is there for a post-mortem. `dir`
+    /// is created if needed and the temporary keeps its file name. If it can't be moved, e.g.
+    /// because `dir` is on another file system, it is left where it is. Either way the new
+    /// location is reported through the [`set_log_hook`](fn.set_log_hook.html) hook.
+    ///
+    /// The default is [`Config::crash_artifacts`](struct.Config.html#structfield.crash_artifacts).
+    /// Temporaries with their own [`fs`](#method.fs) backend are always removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Builder;
+    /// use std::thread;
+    ///
+    /// let artifacts = Builder::new().dir().unwrap();
+    /// let artifacts_path = artifacts.to_path_buf();
+    /// let result = thread::spawn(move || {
+    ///     let _output = Builder::new()
+    ///         .crash_artifacts(&artifacts_path)
+    ///         .file()
+    ///         .unwrap();
+    ///     panic!("test failed");
+    /// })
+    /// .join();
+    /// assert!(result.is_err());
+    /// assert_eq!(artifacts.read_dir().unwrap().count(), 1);
+    /// ```
+    pub fn crash_artifacts<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.crash_artifacts = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Archive the temporary as `<file name>.tar.gz` in `dir` just before it is removed, see
+    /// [`Temp::archive_into`](struct.Temp.html#method.archive_into). Setting this only when a
+    /// CI job has failed leaves a compact artifact of its scratch space to download, without
+    /// the cost for passing runs. Failures to archive are reported through the
+    /// [`set_log_hook`](fn.set_log_hook.html) hook.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Builder;
+    /// use std::env;
+    /// use std::fs;
+    ///
+    /// let artifacts = Builder::new().dir().unwrap();
+    /// let mut builder = Builder::new();
+    /// if env::var_os("CI_JOB_FAILED").is_some() {
+    ///     builder.archive_before_removal(&artifacts);
+    /// }
+    /// let scratch = builder.dir().unwrap();
+    /// fs::write(scratch.join("output.log"), "done").unwrap();
+    /// ```
+    #[cfg(feature = "archive")]
+    pub fn archive_before_removal<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.archive_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Declare that the application removes everything it puts in the temporary directory
+    /// itself, so entries left when the guard is dropped or
+    /// [`close`](struct.Temp.html#method.close)d point to something else writing into it, e.g.
+    /// two components confusing their paths. Cleanup then fails and leaves the directory in
+    /// place for inspection, which panics in [strict](#method.strict) mode. Has no effect on
+    /// files or with a custom [`fs`](#method.fs) backend.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Builder;
+    /// use std::fs;
+    ///
+    /// let work = Builder::new().expect_empty(true).strict(true).dir().unwrap();
+    /// fs::write(work.join("job"), "").unwrap();
+    /// // ...
+    /// fs::remove_file(work.join("job")).unwrap();
+    /// work.close().unwrap();
+    /// ```
+    pub fn expect_empty(&mut self, expect_empty: bool) -> &mut Self {
+        self.expect_empty = expect_empty;
+        self
+    }
+
+    /// Resolve symlinks in the directory the temporary is created in, so its path compares equal
+    /// to the ones other APIs return. On macOS, for example, temporaries end up in
+    /// `/private/var/folders/...` rather than under the `/var` symlink. On Windows the path has
+    /// the `\\?\` prefix, as with
+    /// [`fs::canonicalize`](https://doc.rust-lang.org/std/fs/fn.canonicalize.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Builder;
+    /// use std::fs;
+    ///
+    /// let temp_file = Builder::new().canonicalize(true).file().unwrap();
+    /// assert_eq!(fs::canonicalize(&temp_file).unwrap(), temp_file.path());
+    /// ```
+    pub fn canonicalize(&mut self, canonicalize: bool) -> &mut Self {
+        self.canonicalize = canonicalize;
+        self
+    }
+
+    /// Create a temporary file.
+    #[track_caller]
+    pub fn file(&self) -> io::Result<Temp> {
+        self.create(Kind::File)
+    }
+
+    /// Let child processes inherit the handle opened by [`open_file`](#method.open_file), for a
+    /// child that is meant to use the file through it. Handles are close-on-exec, or not
+    /// inheritable on Windows, by default, so unrelated children spawned meanwhile, possibly
+    /// less trusted or long-lived, can't keep the file open or read it.
+    pub fn inheritable(&mut self, inheritable: bool) -> &mut Self {
+        self.inheritable = inheritable;
+        self
+    }
+
+    /// Create a temporary file and open it for reading and writing, see
+    /// [`TempFile`](struct.TempFile.html). Needs a backend whose files exist on disk.
+    #[track_caller]
+    pub fn open_file(&self) -> io::Result<TempFile> {
+        require_disk(self.backend(), "open_file")?;
+        let temp = self.file()?;
+        // The standard library already opens files close-on-exec.
+        let file = OpenOptions::new().read(true).write(true).open(&temp)?;
+        if self.inheritable {
+            sys::set_inheritable(&file, true)?;
+        }
+        Ok(TempFile::from_parts(file, temp))
+    }
+
+    /// Create a temporary file for a program or script, executable by its owner unless a
+    /// different [`mode`](#method.mode) is set.
+    ///
+    /// Files on a file system mounted `noexec`, as `/tmp` often is on hardened hosts, can't be
+    /// run. If the directory is one, and no directory was given with
+    /// [`in_dir`](#method.in_dir), the first usable candidate from
+    /// [`set_roots`](fn.set_roots.html) that isn't `noexec`, or else the system temp directory,
+    /// is used instead. Otherwise this fails with a
+    /// [`NoExecError`](struct.NoExecError.html).
+    #[track_caller]
+    pub fn executable_file(&self) -> io::Result<Temp> {
+        require_disk(self.backend(), "executable_file")?;
+        let mut builder = self.clone();
+        if builder.mode.is_none() {
+            builder.mode(0o700);
+        }
+
+        let dir = match self.dir {
+            Some(ref dir) => dir.clone(),
+            None => root::checked_root()?,
+        };
+        if !is_noexec(&dir) {
+            return builder.in_dir(dir).file();
+        }
+
+        if self.dir.is_none() {
+            let alternatives = root::candidates().into_iter().chain(Some(env::temp_dir()));
+            for candidate in alternatives {
+                if candidate != dir && !is_noexec(&candidate) && root::probe(&candidate).is_ok() {
+                    return builder.in_dir(candidate).file();
+                }
+            }
+        }
+        Err(NoExecError::new(dir).into())
+    }
+
+    /// Create a temporary file with a logical size of `len` bytes without writing any data.
+    ///
+    /// On file systems that support sparse files no blocks are allocated, so large files can be
+    /// used in tests without consuming disk space. Reads return zeroes. On Windows the file is
+    /// not marked sparse, so NTFS may still reserve the space.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Builder;
+    ///
+    /// let temp_file = Builder::new().sparse_file(1 << 30).unwrap();
+    /// assert_eq!(temp_file.metadata().unwrap().len(), 1 << 30);
+    /// ```
+    #[track_caller]
+    pub fn sparse_file(&self, len: u64) -> io::Result<Temp> {
+        require_disk(self.backend(), "sparse_file")?;
+        let temp = self.file()?;
+        OpenOptions::new().write(true).open(&temp)?.set_len(len)?;
+        Ok(temp)
+    }
+
+    /// Create `count` temporary files, spread across the directories given with
+    /// [`stripe_across`](#method.stripe_across) if any. If one fails, those already created
+    /// are removed.
+    #[track_caller]
+    pub fn files(&self, count: usize) -> io::Result<Vec<Temp>> {
+        (0..count).map(|_| self.create(Kind::File)).collect()
+    }
+
+    /// Create `count` temporary directories, like [`files`](#method.files).
+    #[track_caller]
+    pub fn dirs(&self, count: usize) -> io::Result<Vec<Temp>> {
+        (0..count).map(|_| self.create(Kind::Dir)).collect()
+    }
+
+    /// Create a temporary directory.
+    #[track_caller]
+    pub fn dir(&self) -> io::Result<Temp> {
+        self.create(Kind::Dir)
+    }
+
+    /// Create an uninitialized temporary path, i.e. a file or directory isn't created.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the default directory is rejected because of
+    /// [`set_symlink_free_root`](fn.set_symlink_free_root.html), use
+    /// [`try_path`](#method.try_path) to get the error instead.
+    #[track_caller]
+    pub fn path(&self) -> Temp {
+        match self.try_path() {
+            Ok(temp) => temp,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Create an uninitialized temporary path like [`path`](#method.path), failing instead of
+    /// panicking if the default directory is rejected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Builder;
+    ///
+    /// let socket = Builder::new().extension("sock").try_path().unwrap();
+    /// assert!(!socket.exists());
+    /// ```
+    #[track_caller]
+    pub fn try_path(&self) -> io::Result<Temp> {
+        let path = self.create_path()?;
+        Ok(self.wrap(path, Kind::Path))
+    }
+
+    /// Create a temporary of the given kind.
+    #[track_caller]
+    pub fn build(&self, kind: Kind) -> io::Result<Temp> {
+        match kind {
+            Kind::File => self.file(),
+            Kind::Dir => self.dir(),
+            Kind::Path => self.try_path(),
+        }
+    }
+
+    #[track_caller]
+    fn create(&self, kind: Kind) -> io::Result<Temp> {
+        limit::admit()?;
+        let path = self.create_path()?;
+        match self.create_at(&path, kind) {
+            Ok(()) => Ok(self.wrap(path, kind)),
+            Err(e) => {
+                let dir = path.parent().unwrap_or(&path);
+                if !self.retry_when_full || self.dir.is_some() || !is_unusable(&e) {
+                    return Err(read_only_error(e, dir));
+                }
+                self.create_elsewhere(kind, dir, root::candidates(), e)
+            }
+        }
+    }
+
+    /// Try the candidates after `full`, or all of them if `full` isn't one, in order.
+    #[track_caller]
+    fn create_elsewhere(
+        &self,
+        kind: Kind,
+        full: &Path,
+        candidates: Vec<PathBuf>,
+        error: io::Error,
+    ) -> io::Result<Temp> {
+        let skip = candidates
+            .iter()
+            .position(|candidate| candidate == full)
+            .map_or(0, |i| i + 1);
+
+        let mut full = full.to_path_buf();
+        let mut error = error;
+        for candidate in candidates.into_iter().skip(skip) {
+            log::warn(format_args!(
+                "{} is unusable ({}), retrying in {}",
+                full.display(),
+                error,
+                candidate.display()
+            ));
+
+            let path = self.clone().in_dir(&candidate).create_path()?;
+            match self.create_at(&path, kind) {
+                Ok(()) => return Ok(self.wrap(path, kind)),
+                Err(e) if is_unusable(&e) => {
+                    full = candidate;
+                    error = e;
+                }
+                Err(e) => return Err(read_only_error(e, &candidate)),
+            }
+        }
+        Err(read_only_error(error, &full))
+    }
+
+    pub(crate) fn create_at(&self, path: &Path, kind: Kind) -> io::Result<()> {
+        match kind {
+            Kind::File => self.backend().create_file(path, self.file_mode())?,
+            Kind::Dir => self
+                .backend()
+                .create_dir(path, self.mode.unwrap_or(0o700))?,
+            Kind::Path => return Ok(()),
+        }
+        if self.not_content_indexed && self.fs.is_none() {
+            // Only an optimization, so the temporary is usable without it.
+            let _result = sys::set_not_content_indexed(path);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn create_path(&self) -> io::Result<PathBuf> {
+        let on_disk = self.backend().is_on_disk();
+        let dir = match (&self.dir, &self.stripes) {
+            (Some(dir), _) => dir.clone(),
+            (None, Some(stripes)) => stripes.next(),
+            (None, None) if !on_disk => root::unprobed_root(),
+            (None, None) => root::checked_root()?,
+        };
+        // A directory that can't be resolved fails creation anyway, with a clearer error.
+        let dir = if self.canonicalize && on_disk {
+            fs::canonicalize(&dir).unwrap_or(dir)
+        } else {
+            dir
+        };
+
+        Ok(create_path_in(
+            dir,
+            &self.prefix,
+            self.extension.as_ref().map(|e| &e[..]),
+            self.entropy_bits,
+        ))
+    }
+
+    /// The mode files are created with.
+    pub(crate) fn file_mode(&self) -> u32 {
+        self.mode.unwrap_or(0o600)
+    }
+
+    pub(crate) fn label_arc(&self) -> Option<Arc<str>> {
+        self.label.clone()
+    }
+
+    fn backend(&self) -> &dyn TempFs {
+        match self.fs {
+            Some(ref fs) => &**fs,
+            None => &OsFs,
+        }
+    }
+
+    #[track_caller]
+    pub(crate) fn wrap(&self, path: PathBuf, kind: Kind) -> Temp {
+        let mut temp = self.guard(path, kind);
+        if kind != Kind::Path {
+            temp.live = Some(limit::Live::new());
+        }
+        registry::track(&temp.path, Some(Location::caller()), self.label.clone());
+        #[cfg(feature = "audit")]
+        ::audit::created(&temp);
+        #[cfg(feature = "archive")]
+        {
+            if let Some(ref dir) = self.archive_dir {
+                temp.archive_before_removal(dir.clone());
+            }
+        }
+        temp
+    }
+
+    /// A guard for `path` with this builder's settings, without registering it anywhere.
+    #[track_caller]
+    pub(crate) fn guard(&self, path: PathBuf, kind: Kind) -> Temp {
+        Temp {
+            path,
+            kind,
+            fs: self.fs.clone(),
+            strict: self.strict,
+            sync_on_release: self.sync_on_release,
+            delete_on_reboot: self.delete_on_reboot,
+            parent: None,
+            cleanup_deadline: self.cleanup_deadline,
+            crash_artifacts: self.crash_artifacts.clone(),
+            armed: true,
+            expect_empty: self.expect_empty,
+            frozen: AtomicBool::new(false),
+            label: self.label.clone(),
+            created_at: SystemTime::now(),
+            cleanup_hooks: Mutex::new(Vec::new()),
+            live: None,
+            #[cfg(debug_assertions)]
+            creation_site: Location::caller(),
+        }
+    }
+}
+
+/// The directories set with `Builder::stripe_across`, and whose turn it is.
+#[derive(Debug)]
+struct Stripes {
+    dirs: Vec<PathBuf>,
+    next: AtomicUsize,
+}
+
+impl Stripes {
+    fn next(&self) -> PathBuf {
+        let i = self.next.fetch_add(1, Ordering::Relaxed) % self.dirs.len();
+        self.dirs[i].clone()
+    }
+}
+
+#[cfg(unix)]
+fn is_disk_full(e: &io::Error) -> bool {
+    let code = raw_os_error(e);
+    code == Some(::libc::ENOSPC) || code == Some(::libc::EDQUOT)
+}
+
+#[cfg(windows)]
+fn is_disk_full(e: &io::Error) -> bool {
+    // ERROR_HANDLE_DISK_FULL and ERROR_DISK_FULL
+    let code = raw_os_error(e);
+    code == Some(39) || code == Some(112)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_disk_full(_e: &io::Error) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn is_read_only(e: &io::Error) -> bool {
+    raw_os_error(e) == Some(::libc::EROFS)
+}
+
+#[cfg(windows)]
+fn is_read_only(e: &io::Error) -> bool {
+    // ERROR_WRITE_PROTECT
+    raw_os_error(e) == Some(19)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_read_only(_e: &io::Error) -> bool {
+    false
+}
+
+fn is_unusable(e: &io::Error) -> bool {
+    is_disk_full(e) || is_read_only(e)
+}
+
+/// Replace the opaque OS error for a read-only `dir` with a `ReadOnlyError`.
+fn read_only_error(e: io::Error, dir: &Path) -> io::Error {
+    if is_read_only(&e) {
+        ReadOnlyError::new(dir.to_path_buf()).into()
+    } else {
+        e
+    }
+}
+
+fn is_noexec(dir: &Path) -> bool {
+    sys::is_noexec(dir).unwrap_or(false)
+}
+
+fn assert_no_separator(name: &str) {
+    assert!(
+        !name.chars().any(path::is_separator),
+        "{:?} contains a path separator",
+        name
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use std::os::unix::fs::MetadataExt;
+
+    #[test]
+    fn prefix_and_extension_are_applied() {
+        let temp = Builder::new()
+            .prefix("pre-")
+            .extension("txt")
+            .file()
+            .unwrap();
+        let name = temp.name().to_str().unwrap();
+
+        assert!(name.starts_with("pre-"));
+        assert!(name.ends_with(".txt"));
+    }
+
+    #[test]
+    fn striping_turn_is_shared_by_clones() {
+        let (a, b) = (Temp::new_dir().unwrap(), Temp::new_dir().unwrap());
+        let mut builder = Builder::new();
+        builder.stripe_across(vec![a.to_path_buf(), b.to_path_buf()]);
+        let clone = builder.clone();
+
+        assert!(builder.dir().unwrap().starts_with(&a));
+        assert!(clone.dir().unwrap().starts_with(&b));
+        let dirs = builder.in_dir(&a).dirs(2).unwrap();
+        assert!(dirs.iter().all(|dir| dir.starts_with(&a)));
+    }
+
+    #[test]
+    fn min_entropy_bits_shortens_names() {
+        let temp = Builder::new()
+            .min_entropy_bits(130)
+            .extension("sock")
+            .path();
+        let name = temp.name().to_str().unwrap();
+        let random = name.trim_end_matches(".sock");
+
+        assert_eq!(random.len(), 26);
+        assert!(random.bytes().all(|c| ::ALPHABET.contains(&c)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn min_entropy_bits_has_a_floor() {
+        Builder::new().min_entropy_bits(32);
+    }
+
+    #[test]
+    fn extension_keeps_random_part_after_dotted_prefix() {
+        let temp = Builder::new().prefix("report.v2-").extension("csv").path();
+        let name = temp.name().to_str().unwrap();
+
+        assert!(name.starts_with("report.v2-"));
+        assert!(name.ends_with(".csv"));
+        assert_eq!(name.len(), "report.v2-".len() + 32 + ".csv".len());
+    }
+
+    #[test]
+    #[should_panic]
+    fn prefix_must_not_contain_separator() {
+        Builder::new().prefix("a/b");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn mode_is_applied() {
+        let temp = Builder::new().mode(0o640).file().unwrap();
+        let mode = fs::metadata(&temp).unwrap().mode();
+        assert_eq!(0o640, mode & 0o777);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn executable_file_is_executable() {
+        let temp = match Builder::new().executable_file() {
+            Ok(temp) => temp,
+            // Every temp directory on this host is mounted noexec.
+            Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied => return,
+            Err(e) => panic!("{}", e),
+        };
+
+        let mode = fs::metadata(&temp).unwrap().mode();
+        assert_eq!(0o700, mode & 0o777);
+        assert!(!is_noexec(temp.path().parent().unwrap()));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn full_roots_are_skipped() {
+        use backend::MemFs;
+
+        struct FullIn(PathBuf, MemFs);
+
+        impl TempFs for FullIn {
+            fn create_file(&self, path: &Path, mode: u32) -> io::Result<()> {
+                if path.starts_with(&self.0) {
+                    return Err(io::Error::from_raw_os_error(::libc::ENOSPC));
+                }
+                self.1.create_file(path, mode)
+            }
+
+            fn create_dir(&self, path: &Path, mode: u32) -> io::Result<()> {
+                self.1.create_dir(path, mode)
+            }
+
+            fn remove(&self, path: &Path) -> io::Result<()> {
+                self.1.remove(path)
+            }
+        }
+
+        let (a, b, c) = (
+            PathBuf::from("/a"),
+            PathBuf::from("/b"),
+            PathBuf::from("/c"),
+        );
+        let mut builder = Builder::new();
+        builder
+            .fs(Arc::new(FullIn(b.clone(), MemFs::new())))
+            .retry_when_full(true);
+
+        let enospc = io::Error::from_raw_os_error(::libc::ENOSPC);
+        let candidates = vec![a.clone(), b, c.clone()];
+        let temp = builder
+            .create_elsewhere(Kind::File, &a, candidates, enospc)
+            .unwrap();
+        assert!(temp.starts_with(&c));
+
+        let enospc = io::Error::from_raw_os_error(::libc::ENOSPC);
+        match builder.create_elsewhere(Kind::File, &c, vec![a, c.clone()], enospc) {
+            Err(ref e) if is_disk_full(e) => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn read_only_error_names_dir() {
+        use backend::MemFs;
+
+        struct ReadOnly(MemFs);
+
+        impl TempFs for ReadOnly {
+            fn create_file(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+                Err(io::Error::from_raw_os_error(::libc::EROFS))
+            }
+
+            fn create_dir(&self, path: &Path, mode: u32) -> io::Result<()> {
+                self.0.create_dir(path, mode)
+            }
+
+            fn remove(&self, path: &Path) -> io::Result<()> {
+                self.0.remove(path)
+            }
+        }
+
+        let e = Builder::new()
+            .fs(Arc::new(ReadOnly(MemFs::new())))
+            .in_dir("/ro")
+            .file()
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+        let read_only = e.get_ref().unwrap().downcast_ref::<ReadOnlyError>();
+        assert_eq!(read_only.unwrap().dir(), Path::new("/ro"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn canonicalize_resolves_symlinked_dir() {
+        let dir = Temp::new_dir().unwrap();
+        let link = Temp::new_path();
+        ::std::os::unix::fs::symlink(&dir, &link).unwrap();
+
+        let temp = Builder::new()
+            .in_dir(&link)
+            .canonicalize(true)
+            .file()
+            .unwrap();
+        assert_eq!(
+            temp.parent(),
+            Some(fs::canonicalize(&dir).unwrap().as_path())
+        );
+    }
+
+    #[test]
+    fn sparse_file_has_logical_size() {
+        let len = 1 << 30;
+        let temp = Builder::new().sparse_file(len).unwrap();
+        let metadata = fs::metadata(&temp).unwrap();
+
+        assert_eq!(metadata.len(), len);
+        #[cfg(unix)]
+        assert!(metadata.blocks() * 512 < len);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn open_file_is_close_on_exec_unless_inheritable() {
+        use std::os::unix::io::AsRawFd;
+
+        let cloexec = |file: &TempFile| {
+            let flags = unsafe { ::libc::fcntl(file.as_raw_fd(), ::libc::F_GETFD) };
+            flags & ::libc::FD_CLOEXEC != 0
+        };
+        assert!(cloexec(&Builder::new().open_file().unwrap()));
+        assert!(!cloexec(
+            &Builder::new().inheritable(true).open_file().unwrap()
+        ));
+    }
+
+    #[test]
+    fn build_dispatches_on_kind() {
+        assert!(Builder::new().build(Kind::File).unwrap().is_file());
+        assert!(Builder::new().build(Kind::Dir).unwrap().is_dir());
+        assert!(!Builder::new().build(Kind::Path).unwrap().exists());
+    }
+}
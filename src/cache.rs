@@ -0,0 +1,373 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+
+use path_error::annotate;
+use usage::size;
+use {Builder, SharedTemp, Temp, WeakTemp};
+
+/// A bounded set of temporaries by key, such as thumbnails or transcoded files, which removes
+/// the least recently used ones to make room for new entries.
+///
+/// The cache holds up to `capacity` entries and, if set with [`max_bytes`](#method.max_bytes),
+/// up to that many bytes on disk. Sizes are measured when an entry is added, so an entry
+/// written afterwards counts in full from the next insertion or [`trim`](#method.trim) on. The
+/// newest entry is never evicted, even if it alone is over the limit.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::TempCache;
+/// use std::fs;
+///
+/// let mut thumbnails = TempCache::new(2);
+/// for name in &["a.png", "b.png", "c.png"] {
+///     let file = thumbnails.create_file(name.to_string()).unwrap();
+///     fs::write(file, b"pixels").unwrap();
+/// }
+///
+/// assert!(thumbnails.get("a.png").is_none());
+/// assert!(thumbnails.get("c.png").is_some());
+/// ```
+pub struct TempCache<K: Hash + Eq> {
+    entries: HashMap<K, Entry>,
+    builder: Builder,
+    capacity: usize,
+    max_bytes: Option<u64>,
+    clock: u64,
+    // Files created by `insert_content` by the hash of their content, if content addressed.
+    blobs: Option<HashMap<u64, Vec<WeakTemp>>>,
+}
+
+struct Entry {
+    slot: Slot,
+    last_used: u64,
+}
+
+enum Slot {
+    Owned(Temp),
+    Shared(SharedTemp),
+}
+
+impl Entry {
+    fn temp(&self) -> &Temp {
+        match self.slot {
+            Slot::Owned(ref temp) => temp,
+            Slot::Shared(ref shared) => shared,
+        }
+    }
+}
+
+impl<K: Hash + Eq + fmt::Debug> fmt::Debug for TempCache<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TempCache")
+            .field(
+                "entries",
+                &self
+                    .entries
+                    .iter()
+                    .map(|(key, entry)| (key, entry.temp()))
+                    .collect::<Vec<_>>(),
+            )
+            .field("capacity", &self.capacity)
+            .field("max_bytes", &self.max_bytes)
+            .finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone> TempCache<K> {
+    /// Create a cache holding up to `capacity` entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero, as the newest entry is always kept.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_builder(Builder::new(), capacity)
+    }
+
+    /// Create a cache holding up to `capacity` entries, creating new ones using `builder`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero, as the newest entry is always kept.
+    pub fn with_builder(builder: Builder, capacity: usize) -> Self {
+        assert!(capacity != 0, "capacity must not be zero");
+        TempCache {
+            entries: HashMap::with_capacity(capacity),
+            builder,
+            capacity,
+            max_bytes: None,
+            clock: 0,
+            blobs: None,
+        }
+    }
+
+    /// Also limit the total size of the entries on disk to `max_bytes`.
+    pub fn max_bytes(&mut self, max_bytes: u64) -> &mut Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Whether [`insert_content`](#method.insert_content) reuses a file already holding the same
+    /// content instead of writing a new one, to save disk when the same blobs are materialized
+    /// over and over. Off by default.
+    pub fn content_addressed(&mut self, enabled: bool) -> &mut Self {
+        self.blobs = if enabled { Some(HashMap::new()) } else { None };
+        self
+    }
+
+    /// Add `temp` under `key`, evicting least recently used entries if the cache is then over
+    /// its limits. An entry already under `key` is removed.
+    pub fn insert(&mut self, key: K, temp: Temp) -> &Temp {
+        self.insert_slot(key, Slot::Owned(temp))
+    }
+
+    fn insert_slot(&mut self, key: K, slot: Slot) -> &Temp {
+        self.clock += 1;
+        let entry = Entry {
+            slot,
+            last_used: self.clock,
+        };
+        self.entries.insert(key.clone(), entry);
+        self.trim();
+        self.entries[&key].temp()
+    }
+
+    /// Add a temporary file holding `content` under `key`, see [`insert`](#method.insert), and
+    /// return a handle to it which keeps the file alive after it is evicted.
+    ///
+    /// If the cache is [`content_addressed`](#method.content_addressed) and a file created this
+    /// way with the same content is still alive, under any key or only through handles, that
+    /// file is returned instead of writing a new one. The file is removed when the last handle
+    /// to it is dropped, so it must not be modified. A file shared by several keys counts
+    /// toward [`max_bytes`](#method.max_bytes) for each of them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::TempCache;
+    ///
+    /// let mut blobs = TempCache::new(10);
+    /// blobs.content_addressed(true);
+    /// let a = blobs.insert_content("a", b"blob").unwrap();
+    /// let b = blobs.insert_content("b", b"blob").unwrap();
+    /// assert_eq!(a.path(), b.path());
+    /// ```
+    #[track_caller]
+    pub fn insert_content(&mut self, key: K, content: &[u8]) -> io::Result<SharedTemp> {
+        let shared = match self.blobs {
+            Some(ref mut blobs) => {
+                let mut hasher = DefaultHasher::new();
+                content.hash(&mut hasher);
+                // Forget the files that are gone, and the hashes left without any.
+                blobs.retain(|_, same_hash| {
+                    same_hash.retain(WeakTemp::is_alive);
+                    !same_hash.is_empty()
+                });
+                let same_hash = blobs.entry(hasher.finish()).or_insert_with(Vec::new);
+                match find_blob(same_hash, content) {
+                    Some(shared) => shared,
+                    None => {
+                        let shared = SharedTemp::new(write_blob(&self.builder, content)?);
+                        same_hash.push(shared.downgrade());
+                        shared
+                    }
+                }
+            }
+            None => SharedTemp::new(write_blob(&self.builder, content)?),
+        };
+        self.insert_slot(key, Slot::Shared(shared.clone()));
+        Ok(shared)
+    }
+
+    /// Create an empty temporary file under `key`, see [`insert`](#method.insert).
+    #[track_caller]
+    pub fn create_file(&mut self, key: K) -> io::Result<&Temp> {
+        let temp = self.builder.file()?;
+        Ok(self.insert(key, temp))
+    }
+
+    /// Create an empty temporary directory under `key`, see [`insert`](#method.insert).
+    #[track_caller]
+    pub fn create_dir(&mut self, key: K) -> io::Result<&Temp> {
+        let temp = self.builder.dir()?;
+        Ok(self.insert(key, temp))
+    }
+
+    /// Look up the entry under `key`, marking it as the most recently used.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&Temp>
+    where
+        K: ::std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.clock += 1;
+        let clock = self.clock;
+        self.entries.get_mut(key).map(|entry| {
+            entry.last_used = clock;
+            entry.temp()
+        })
+    }
+
+    /// Take the entry under `key` out of the cache, leaving it to the caller to remove.
+    ///
+    /// An entry added with [`insert_content`](#method.insert_content) is only returned if the
+    /// cache held the last handle to it; otherwise the cache lets go of its handle and the file
+    /// lives on with the others.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<Temp>
+    where
+        K: ::std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.entries.remove(key).and_then(|entry| match entry.slot {
+            Slot::Owned(temp) => Some(temp),
+            Slot::Shared(shared) => shared.try_unwrap().ok(),
+        })
+    }
+
+    /// Evict least recently used entries until the cache is within its limits, measuring the
+    /// size of every entry again.
+    pub fn trim(&mut self) {
+        while self.entries.len() > self.capacity {
+            self.evict();
+        }
+        let max_bytes = match self.max_bytes {
+            Some(max_bytes) => max_bytes,
+            None => return,
+        };
+
+        let mut by_age: Vec<(u64, K, u64)> = self
+            .entries
+            .iter()
+            .map(|(key, entry)| {
+                (
+                    entry.last_used,
+                    key.clone(),
+                    size(entry.temp()).unwrap_or(0),
+                )
+            })
+            .collect();
+        by_age.sort_by_key(|entry| entry.0);
+        let mut total: u64 = by_age.iter().map(|entry| entry.2).sum();
+        // Leave the newest entry, even if it is over the limit by itself.
+        for (_, key, bytes) in by_age
+            .into_iter()
+            .rev()
+            .skip(1)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+        {
+            if total <= max_bytes {
+                break;
+            }
+            self.entries.remove(&key);
+            total -= bytes;
+        }
+    }
+
+    /// Remove the least recently used entry.
+    fn evict(&mut self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|entry| entry.1.last_used)
+            .map(|entry| entry.0.clone());
+        if let Some(key) = oldest {
+            self.entries.remove(&key);
+        }
+    }
+
+    /// The number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// A live file among `same_hash` holding exactly `content`.
+fn find_blob(same_hash: &[WeakTemp], content: &[u8]) -> Option<SharedTemp> {
+    same_hash
+        .iter()
+        .filter_map(WeakTemp::upgrade)
+        .find(|shared| fs::read(shared.path()).ok().as_deref() == Some(content))
+}
+
+#[track_caller]
+fn write_blob(builder: &Builder, content: &[u8]) -> io::Result<Temp> {
+    let temp = builder.file()?;
+    annotate(fs::write(&temp, content), "write", &temp)?;
+    Ok(temp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        let mut cache = TempCache::new(2);
+        let a = cache.create_file("a").unwrap().to_path_buf();
+        cache.create_file("b").unwrap();
+        cache.get("a").unwrap();
+        let c = cache.create_file("c").unwrap().to_path_buf();
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("b").is_none());
+        assert!(a.exists() && c.exists());
+    }
+
+    #[test]
+    fn evicted_by_size() {
+        let mut cache = TempCache::new(10);
+        cache.max_bytes(15);
+        for key in 0..3 {
+            let file = cache.create_file(key).unwrap();
+            fs::write(file, [0; 10]).unwrap();
+        }
+
+        assert_eq!(cache.len(), 2);
+        cache.trim();
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&2).is_some());
+    }
+
+    #[test]
+    fn identical_content_is_shared() {
+        let mut cache = TempCache::new(1);
+        cache.content_addressed(true);
+        let a = cache.insert_content("a", b"blob").unwrap();
+        let b = cache.insert_content("b", b"blob").unwrap();
+        let other = cache.insert_content("c", b"other").unwrap();
+        assert_eq!(a.path(), b.path());
+        assert_ne!(a.path(), other.path());
+
+        // Evicted from the cache, but the handle keeps the file alive.
+        assert!(cache.get("a").is_none());
+        let path = a.to_path_buf();
+        drop((a, b));
+        assert!(!path.exists());
+        assert!(cache.remove("c").is_none());
+        assert!(other.exists());
+
+        // The hash of the removed file is forgotten.
+        let _d = cache.insert_content("d", b"new").unwrap();
+        assert_eq!(cache.blobs.as_ref().unwrap().len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity must not be zero")]
+    fn zero_capacity_is_rejected() {
+        TempCache::<u32>::new(0);
+    }
+}
@@ -0,0 +1,301 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use {Kind, Temp, DRY_RUN};
+
+/// Cancels the background cleanups it is given to, see
+/// [`Temp::close_in_background_with`](struct.Temp.html#method.close_in_background_with).
+/// Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// A token that hasn't been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop the cleanups using this token, or any of its clones, before their next entry.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether [`cancel`](#method.cancel) has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn check(&self, path: &Path) -> io::Result<()> {
+        if self.is_cancelled() {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                format!("removal of {} was cancelled", path.display()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A handle to a cleanup running in the background, see
+/// [`Temp::close_in_background`](struct.Temp.html#method.close_in_background).
+///
+/// Dropping the handle doesn't cancel the cleanup.
+#[derive(Debug)]
+pub struct CleanupHandle {
+    done: Receiver<io::Result<()>>,
+}
+
+impl CleanupHandle {
+    pub(crate) fn spawn(temp: Temp) -> Self {
+        Self::spawn_with(temp, Temp::close)
+    }
+
+    /// Remove `temp` with `close` on a new thread.
+    pub(crate) fn spawn_with(temp: Temp, close: fn(Temp) -> io::Result<()>) -> Self {
+        let (sender, done) = mpsc::channel();
+
+        thread::spawn(move || {
+            // The handle may already be gone, in which case nobody is waiting for the result.
+            let _result = sender.send(close(temp));
+        });
+
+        CleanupHandle { done }
+    }
+
+    pub(crate) fn spawn_cancellable(temp: Temp, token: CancelToken) -> Self {
+        let (sender, done) = mpsc::channel();
+
+        thread::spawn(move || {
+            let result = remove_cancellable(&temp, &token);
+            let _result = sender.send(temp.closed(result));
+        });
+
+        CleanupHandle { done }
+    }
+
+    /// Block until the cleanup has finished.
+    pub fn wait(self) -> io::Result<()> {
+        self.done.recv().unwrap_or_else(|_| Err(panicked()))
+    }
+
+    /// Block until the cleanup has finished or `timeout` has passed, returning the result if it
+    /// finished.
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<io::Result<()>> {
+        match self.done.recv_timeout(timeout) {
+            Ok(result) => Some(result),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => Some(Err(panicked())),
+        }
+    }
+
+    /// Return the result of the cleanup if it has finished, without blocking.
+    pub fn try_wait(&self) -> Option<io::Result<()>> {
+        match self.done.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(panicked())),
+        }
+    }
+}
+
+/// Remove a temporary, checking `token` before each entry of a directory on the real file
+/// system. Other backends can only be stopped before they start.
+fn remove_cancellable(temp: &Temp, token: &CancelToken) -> io::Result<()> {
+    token.check(&temp.path)?;
+    // While the contents are still there.
+    temp.run_cleanup_hooks();
+    if temp.kind == Kind::Dir && temp.fs.is_none() && !DRY_RUN.load(Ordering::Relaxed) {
+        temp.check_expected_empty()?;
+        temp.thaw_for_removal();
+        remove_contents(&temp.path, token)?;
+    }
+    temp.cleanup()
+}
+
+fn remove_contents(dir: &Path, token: &CancelToken) -> io::Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        entries => entries?,
+    };
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        token.check(&path)?;
+
+        // Symlinks to directories are removed, not followed.
+        let result = if entry.file_type()?.is_dir() {
+            remove_contents(&path, token).and_then(|()| fs::remove_dir(&path))
+        } else {
+            fs::remove_file(&path)
+        };
+        match result {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+fn panicked() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "cleanup thread panicked")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::TempFs;
+    use std::fs;
+    use std::path::Path;
+    use std::time::Instant;
+    use Builder;
+
+    #[derive(Default)]
+    struct SlowFs {
+        removed: AtomicBool,
+    }
+
+    impl TempFs for SlowFs {
+        fn create_file(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn create_dir(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn remove(&self, _path: &Path) -> io::Result<()> {
+            thread::sleep(Duration::from_millis(500));
+            self.removed.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn cancelled_cleanup_leaves_the_rest() {
+        let dir = Temp::new_dir().unwrap();
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("file"), b"data").unwrap();
+        let path = dir.to_path_buf();
+
+        let token = CancelToken::new();
+        remove_contents(&path, &token).unwrap();
+        assert!(!path.join("sub").exists());
+
+        fs::write(path.join("file"), b"data").unwrap();
+        token.cancel();
+        let clone = token.clone();
+        assert!(clone.is_cancelled());
+        match dir.close_in_background_with(&clone).wait() {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            result => panic!("{:?}", result),
+        }
+        assert!(path.join("file").exists());
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn hooks_see_the_contents_before_a_cancellable_cleanup() {
+        let dir = Temp::new_dir().unwrap();
+        fs::write(dir.join("file"), b"data").unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        dir.on_cleanup(move |path| {
+            sender.send(fs::read_dir(path).unwrap().count()).unwrap();
+        });
+        dir.close_in_background_with(&CancelToken::new())
+            .wait()
+            .unwrap();
+        assert_eq!(receiver.recv().unwrap(), 1);
+    }
+
+    #[test]
+    fn frozen_dir_is_removed_by_a_cancellable_cleanup() {
+        let dir = Temp::new_dir().unwrap();
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("file"), b"data").unwrap();
+        dir.freeze().unwrap();
+        let path = dir.to_path_buf();
+
+        dir.close_in_background_with(&CancelToken::new())
+            .wait()
+            .unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn cancellable_cleanup_keeps_unexpected_entries() {
+        let dir = Builder::new().expect_empty(true).dir().unwrap();
+        fs::write(dir.join("stray"), b"").unwrap();
+        let path = dir.to_path_buf();
+
+        let e = dir
+            .close_in_background_with(&CancelToken::new())
+            .wait()
+            .unwrap_err();
+        assert!(e.to_string().contains("stray"));
+        assert!(path.join("stray").exists());
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn slow_cleanup_continues_in_background() {
+        let fs = Arc::new(SlowFs::default());
+        let temp = Builder::new()
+            .fs(fs.clone())
+            .cleanup_deadline(Duration::from_millis(10))
+            .dir()
+            .unwrap();
+
+        let start = Instant::now();
+        drop(temp);
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(!fs.removed.load(Ordering::SeqCst));
+
+        while Arc::strong_count(&fs) > 1 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(fs.removed.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn slow_close_times_out() {
+        let fs = Arc::new(SlowFs::default());
+        let temp = Builder::new()
+            .fs(fs.clone())
+            .cleanup_deadline(Duration::from_millis(10))
+            .file()
+            .unwrap();
+
+        match temp.close() {
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => (),
+            result => panic!("{:?}", result),
+        }
+        assert!(!fs.removed.load(Ordering::SeqCst));
+
+        while Arc::strong_count(&fs) > 1 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(fs.removed.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn wait_for_background_cleanup() {
+        let dir = Temp::new_dir().unwrap();
+        fs::write(dir.join("file"), b"data").unwrap();
+        let path = dir.to_path_buf();
+
+        dir.close_in_background().wait().unwrap();
+        assert!(!path.exists());
+    }
+}
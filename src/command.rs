@@ -0,0 +1,77 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::Command;
+
+use Temp;
+
+impl Temp {
+    /// Run `command` inside this temporary directory, returning it for further configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mktemp::Temp;
+    /// use std::process::Command;
+    ///
+    /// let scratch = Temp::new_dir().unwrap();
+    /// let status = scratch
+    ///     .run_in(&mut Command::new("tool-under-test"))
+    ///     .arg("--output=result.json")
+    ///     .status()
+    ///     .unwrap();
+    /// assert!(status.success());
+    /// assert!(scratch.join("result.json").exists());
+    /// ```
+    pub fn run_in<'c>(&self, command: &'c mut Command) -> &'c mut Command {
+        command.current_dir(&self.path)
+    }
+
+    /// Set the environment variable `key` of `command` to `relative` inside this temporary
+    /// directory, e.g. to point `HOME` or `XDG_CONFIG_HOME` of the program under test at a
+    /// scratch location. The path doesn't have to exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::process::Command;
+    ///
+    /// let scratch = Temp::new_dir().unwrap();
+    /// let mut command = Command::new("tool-under-test");
+    /// scratch.env_in(scratch.run_in(&mut command), "HOME", "home");
+    /// ```
+    pub fn env_in<'c, K, P>(&self, command: &'c mut Command, key: K, relative: P) -> &'c mut Command
+    where
+        K: AsRef<OsStr>,
+        P: AsRef<Path>,
+    {
+        command.env(key, self.path.join(relative))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_runs_inside_temp() {
+        let dir = Temp::new_dir().unwrap();
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("pwd > cwd; printf %s \"$DATA\" > data");
+        dir.env_in(dir.run_in(&mut command), "DATA", "data");
+        assert!(command.status().unwrap().success());
+
+        let cwd = ::std::fs::read_to_string(dir.join("cwd")).unwrap();
+        assert_eq!(
+            Path::new(cwd.trim()).canonicalize().unwrap(),
+            dir.canonicalize().unwrap()
+        );
+        let data = ::std::fs::read_to_string(dir.join("data")).unwrap();
+        assert_eq!(Path::new(&data), dir.join("data"));
+    }
+}
@@ -0,0 +1,185 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::io;
+use std::path::{self, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use {
+    set_creation_limit, set_dry_run, set_local_fallback, set_roots, set_secure_system_temp,
+    set_strict, set_symlink_free_root, CreationLimit,
+};
+
+/// How generated names are composed, after the prefix.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Naming {
+    /// A random UUID, the default.
+    #[default]
+    Random,
+    /// The creation time in seconds since the Unix epoch followed by a random UUID, so that
+    /// temporaries sort by age.
+    Timestamped,
+    /// The host name, process ID and a per-process counter followed by a random UUID, the
+    /// maildir convention, so temporaries in a directory shared between hosts, e.g. over NFS,
+    /// can't collide and can be traced back to the process that created them.
+    HostUnique,
+}
+
+/// What happens to a temporary when it is dropped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CleanupPolicy {
+    /// Remove it, the default.
+    #[default]
+    Remove,
+    /// Leave it in place, e.g. to inspect the temporaries of a failed run.
+    Keep,
+    /// Record it instead of removing it, see [`set_dry_run`](fn.set_dry_run.html).
+    DryRun,
+}
+
+/// Crate-wide defaults, applied once at startup with [`configure`](fn.configure.html).
+///
+/// The settings apply to every temporary, including those created with the simple
+/// constructors by dependencies, except where a [`Builder`](struct.Builder.html) overrides
+/// them. Temporaries with their own [`Builder::fs`](struct.Builder.html#method.fs) backend
+/// follow their backend instead of the cleanup policy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// The directory to create temporaries in, if usable, see
+    /// [`set_roots`](fn.set_roots.html).
+    pub root: Option<PathBuf>,
+    /// A directory to create a `.tmp` directory in if the system temp directory is unusable,
+    /// see [`set_local_fallback`](fn.set_local_fallback.html).
+    pub local_fallback: Option<PathBuf>,
+    /// The prefix of generated names.
+    pub prefix: Option<String>,
+    /// How generated names are composed.
+    pub naming: Naming,
+    /// What happens to temporaries when they are dropped.
+    pub cleanup_policy: CleanupPolicy,
+    /// Panic in debug builds when cleanup fails, see [`set_strict`](fn.set_strict.html).
+    pub strict: bool,
+    /// Find the system temp directory with `GetTempPath2W` on Windows, see
+    /// [`set_secure_system_temp`](fn.set_secure_system_temp.html).
+    pub secure_system_temp: bool,
+    /// Refuse a temp directory reached through other users' symlinks, see
+    /// [`set_symlink_free_root`](fn.set_symlink_free_root.html).
+    pub symlink_free_root: bool,
+    /// The directory to move temporaries into if they are dropped while the thread is
+    /// panicking, see [`Builder::crash_artifacts`](struct.Builder.html#method.crash_artifacts).
+    pub crash_artifacts: Option<PathBuf>,
+    /// Limits on creating temporaries, see
+    /// [`set_creation_limit`](fn.set_creation_limit.html).
+    pub creation_limit: Option<CreationLimit>,
+}
+
+static CONFIG: Mutex<Option<Config>> = Mutex::new(None);
+static NAMING: AtomicU8 = AtomicU8::new(Naming::Random as u8);
+static KEEP: AtomicBool = AtomicBool::new(false);
+
+fn current() -> MutexGuard<'static, Option<Config>> {
+    CONFIG.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Set the crate-wide defaults. This can only be done once, later calls fail with
+/// `AlreadyExists`, so an application's settings can't be changed behind its back.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::{CleanupPolicy, Config, Naming, Temp};
+///
+/// mktemp::configure(Config {
+///     prefix: Some("myapp-".into()),
+///     naming: Naming::Timestamped,
+///     cleanup_policy: CleanupPolicy::Remove,
+///     ..Config::default()
+/// })
+/// .unwrap();
+///
+/// let temp_file = Temp::new_file().unwrap();
+/// assert!(temp_file.name().to_str().unwrap().starts_with("myapp-"));
+/// assert!(mktemp::configure(Config::default()).is_err());
+/// ```
+pub fn configure(config: Config) -> io::Result<()> {
+    let mut current = current();
+    if current.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "mktemp is already configured",
+        ));
+    }
+    if let Some(ref prefix) = config.prefix {
+        if prefix.chars().any(path::is_separator) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} contains a path separator", prefix),
+            ));
+        }
+    }
+    if let Some(limit) = config.creation_limit {
+        if limit.per_second == Some(0) || limit.max_live == Some(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a creation limit must not be zero",
+            ));
+        }
+    }
+
+    if let Some(ref root) = config.root {
+        set_roots(vec![root.clone()]);
+    }
+    if config.local_fallback.is_some() {
+        set_local_fallback(config.local_fallback.clone());
+    }
+    set_strict(config.strict);
+    set_secure_system_temp(config.secure_system_temp);
+    set_symlink_free_root(config.symlink_free_root);
+    set_dry_run(config.cleanup_policy == CleanupPolicy::DryRun);
+    if config.creation_limit.is_some() {
+        set_creation_limit(config.creation_limit);
+    }
+    KEEP.store(
+        config.cleanup_policy == CleanupPolicy::Keep,
+        Ordering::Relaxed,
+    );
+    NAMING.store(config.naming as u8, Ordering::Relaxed);
+
+    *current = Some(config);
+    Ok(())
+}
+
+/// The configuration set with [`configure`](fn.configure.html), if any.
+pub fn config() -> Option<Config> {
+    current().clone()
+}
+
+pub(crate) fn default_prefix() -> String {
+    match *current() {
+        Some(Config {
+            prefix: Some(ref prefix),
+            ..
+        }) => prefix.clone(),
+        _ => String::new(),
+    }
+}
+
+pub(crate) fn crash_artifacts() -> Option<PathBuf> {
+    match *current() {
+        Some(ref config) => config.crash_artifacts.clone(),
+        None => None,
+    }
+}
+
+pub(crate) fn keep() -> bool {
+    KEEP.load(Ordering::Relaxed)
+}
+
+pub(crate) fn naming() -> Naming {
+    match NAMING.load(Ordering::Relaxed) {
+        n if n == Naming::Timestamped as u8 => Naming::Timestamped,
+        n if n == Naming::HostUnique as u8 => Naming::HostUnique,
+        _ => Naming::Random,
+    }
+}
@@ -0,0 +1,154 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use root::{self, TEMP_DIR_VARS};
+
+/// Where the default directory for temporaries came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RootSource {
+    /// [`with_root`](fn.with_root.html) on the current thread.
+    ThreadOverride,
+    /// A live [`RunDir`](struct.RunDir.html).
+    RunDir,
+    /// A candidate set with [`set_roots`](fn.set_roots.html) or
+    /// [`configure`](fn.configure.html).
+    Candidate,
+    /// The named environment variable, read by `std::env::temp_dir`.
+    EnvVar(&'static str),
+    /// The platform's default, e.g. `/tmp`, since none of the environment variables are set.
+    PlatformDefault,
+    /// The `.tmp` directory set with [`set_local_fallback`](fn.set_local_fallback.html), since
+    /// the system temp directory is unusable.
+    LocalFallback,
+}
+
+/// A report of how the directory for temporaries was resolved and whether it is usable, see
+/// [`diagnostics`](fn.diagnostics.html). Its `Display` output is meant to be pasted into bug
+/// reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostics {
+    /// The directory temporaries are created in by default.
+    pub root: PathBuf,
+    /// Where `root` came from.
+    pub source: RootSource,
+    /// The environment variables the system temp directory is read from, in order of
+    /// precedence, with their values.
+    pub env: Vec<(&'static str, Option<OsString>)>,
+    /// The candidates set with [`set_roots`](fn.set_roots.html), in order.
+    pub candidates: Vec<PathBuf>,
+    /// What's wrong with `root`, if a temporary can't be created in it.
+    pub problem: Option<String>,
+}
+
+/// Report how the directory for temporaries is resolved on this machine, and check that
+/// temporaries can be created in it.
+///
+/// # Examples
+///
+/// ```
+/// let diagnostics = mktemp::diagnostics();
+/// assert_eq!(diagnostics.root, mktemp::root());
+/// assert!(diagnostics.problem.is_none());
+/// println!("{}", diagnostics);
+/// ```
+pub fn diagnostics() -> Diagnostics {
+    let (root, source) = root::resolve();
+    let env = TEMP_DIR_VARS
+        .iter()
+        .map(|&name| (name, env::var_os(name)))
+        .collect();
+    let problem = problem(&root);
+
+    Diagnostics {
+        root,
+        source,
+        env,
+        candidates: root::candidates(),
+        problem,
+    }
+}
+
+fn problem(root: &Path) -> Option<String> {
+    let metadata = match fs::metadata(root) {
+        Ok(metadata) => metadata,
+        Err(e) => return Some(format!("can't be read: {}", e)),
+    };
+    if !metadata.is_dir() {
+        return Some("is not a directory".to_string());
+    }
+    if let Err(e) = root::probe(root) {
+        return Some(format!("a file can't be created in it: {}", e));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = metadata.permissions().mode();
+        if mode & 0o002 != 0 && mode & 0o1000 == 0 {
+            return Some("is writable by everyone without the sticky bit".to_string());
+        }
+    }
+    None
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "root: {}", self.root.display())?;
+        match self.source {
+            RootSource::ThreadOverride => writeln!(f, "source: with_root on this thread")?,
+            RootSource::RunDir => writeln!(f, "source: run directory")?,
+            RootSource::Candidate => writeln!(f, "source: configured candidate")?,
+            RootSource::EnvVar(name) => writeln!(f, "source: environment variable {}", name)?,
+            RootSource::PlatformDefault => writeln!(f, "source: platform default")?,
+            RootSource::LocalFallback => writeln!(f, "source: local fallback")?,
+        }
+        for &(name, ref value) in &self.env {
+            match *value {
+                Some(ref value) => writeln!(f, "{}={}", name, value.to_string_lossy())?,
+                None => writeln!(f, "{} is not set", name)?,
+            }
+        }
+        for candidate in &self.candidates {
+            writeln!(f, "candidate: {}", candidate.display())?;
+        }
+        match self.problem {
+            Some(ref problem) => write!(f, "problem: the root {}", problem),
+            None => write!(f, "the root is usable"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {with_root, Temp};
+
+    #[test]
+    fn reports_thread_override() {
+        let dir = Temp::new_dir().unwrap();
+        let diagnostics = with_root(&dir, diagnostics);
+
+        assert_eq!(diagnostics.root, dir.to_path_buf());
+        assert_eq!(diagnostics.source, RootSource::ThreadOverride);
+        assert_eq!(diagnostics.problem, None);
+    }
+
+    #[test]
+    fn reports_missing_root() {
+        let dir = Temp::new_dir().unwrap();
+        let missing = dir.join("missing");
+        let diagnostics = with_root(&missing, diagnostics);
+
+        assert!(diagnostics.problem.is_some());
+        assert!(diagnostics
+            .to_string()
+            .contains("problem: the root can't be read"));
+    }
+}
@@ -0,0 +1,151 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use tokio::fs::{self, DirEntry, ReadDir};
+
+use path_error::annotate;
+use typed::TempDir;
+
+type Opening = Pin<Box<dyn Future<Output = io::Result<ReadDir>> + Send>>;
+
+enum State {
+    Opening(Opening),
+    Reading(ReadDir),
+    Done,
+}
+
+/// The entries of a temporary directory, read without blocking the executor, returned by
+/// [`TempDir::entries_stream`](struct.TempDir.html#method.entries_stream).
+///
+/// It is a `Stream` of `io::Result<DirEntry>`; failing to open the directory is its first and
+/// only item.
+pub struct EntriesStream {
+    path: PathBuf,
+    state: State,
+}
+
+impl EntriesStream {
+    /// A future of the next entry, or `None` when there are no more, for use without a
+    /// `Stream` combinator library.
+    pub fn next_entry(&mut self) -> NextEntry<'_> {
+        NextEntry { entries: self }
+    }
+}
+
+impl fmt::Debug for EntriesStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EntriesStream")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl Stream for EntriesStream {
+    type Item = io::Result<DirEntry>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let entries = self.get_mut();
+        loop {
+            let result = match entries.state {
+                State::Opening(ref mut opening) => match opening.as_mut().poll(cx) {
+                    Poll::Ready(Ok(read_dir)) => {
+                        entries.state = State::Reading(read_dir);
+                        continue;
+                    }
+                    Poll::Ready(Err(e)) => {
+                        entries.state = State::Done;
+                        Err(e)
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+                State::Reading(ref mut read_dir) => match read_dir.poll_next_entry(cx) {
+                    Poll::Ready(Ok(Some(entry))) => Ok(entry),
+                    Poll::Ready(Ok(None)) => return Poll::Ready(None),
+                    Poll::Ready(Err(e)) => Err(e),
+                    Poll::Pending => return Poll::Pending,
+                },
+                State::Done => return Poll::Ready(None),
+            };
+            return Poll::Ready(Some(annotate(result, "read directory", &entries.path)));
+        }
+    }
+}
+
+/// The future returned by [`EntriesStream::next_entry`](struct.EntriesStream.html#method.next_entry).
+#[derive(Debug)]
+pub struct NextEntry<'a> {
+    entries: &'a mut EntriesStream,
+}
+
+impl<'a> Future for NextEntry<'a> {
+    type Output = io::Result<Option<DirEntry>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::new(&mut *self.get_mut().entries).poll_next(cx) {
+            Poll::Ready(Some(Ok(entry))) => Poll::Ready(Ok(Some(entry))),
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Err(e)),
+            Poll::Ready(None) => Poll::Ready(Ok(None)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl TempDir {
+    /// A `Stream` of the entries of the directory, read with `tokio::fs::read_dir`, so an async
+    /// service can go through a large scratch directory without blocking. It must be polled
+    /// within a tokio runtime.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate mktemp;
+    /// # extern crate tokio;
+    /// use mktemp::TempDir;
+    /// use std::fs;
+    ///
+    /// # fn main() {
+    /// let scratch = TempDir::new().unwrap();
+    /// fs::write(scratch.child("a"), "").unwrap();
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    /// let mut entries = scratch.entries_stream();
+    /// let mut names = Vec::new();
+    /// while let Some(entry) = runtime.block_on(entries.next_entry()).unwrap() {
+    ///     names.push(entry.file_name());
+    /// }
+    /// assert_eq!(names, ["a"]);
+    /// # }
+    /// ```
+    pub fn entries_stream(&self) -> EntriesStream {
+        EntriesStream {
+            path: self.path().to_path_buf(),
+            state: State::Opening(Box::pin(fs::read_dir(self.path().to_path_buf()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_dir_fails_once() {
+        let runtime = ::tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let temp = TempDir::new().unwrap();
+        ::std::fs::remove_dir(temp.path()).unwrap();
+        let mut entries = temp.entries_stream();
+        let result = runtime.block_on(entries.next_entry());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert!(runtime.block_on(entries.next_entry()).unwrap().is_none());
+    }
+}
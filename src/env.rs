@@ -0,0 +1,74 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::env;
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+
+use Temp;
+
+const VARS: &[&str] = &["TMPDIR", "TEMP", "TMP"];
+
+/// Points `TMPDIR`, `TEMP` and `TMP` at a new temporary directory for as long as it is alive,
+/// so subprocesses spawned meanwhile, and anything else that asks the environment, create their
+/// temporaries in it. Dropping it restores the previous values and removes the directory.
+///
+/// The environment is shared by the whole process: other threads see the change too, and
+/// nested guards have to be dropped in reverse order.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::TempEnv;
+/// use std::env;
+///
+/// let before = env::temp_dir();
+/// {
+///     let confined = TempEnv::new().unwrap();
+///     assert_eq!(env::temp_dir(), confined.path());
+///     // std::process::Command::new("tool-under-test").status().unwrap();
+/// }
+/// assert_eq!(env::temp_dir(), before);
+/// ```
+#[derive(Debug)]
+pub struct TempEnv {
+    previous: Vec<(&'static str, Option<OsString>)>,
+    // Dropped after the variables are restored.
+    dir: Temp,
+}
+
+impl TempEnv {
+    /// Create the directory and point the environment at it.
+    #[track_caller]
+    pub fn new() -> io::Result<Self> {
+        let dir = Temp::new_dir()?;
+        let previous = VARS.iter().map(|&name| (name, env::var_os(name))).collect();
+        for name in VARS {
+            env::set_var(name, dir.path());
+        }
+        Ok(TempEnv { previous, dir })
+    }
+
+    /// The directory the environment points at.
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl AsRef<Path> for TempEnv {
+    fn as_ref(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for TempEnv {
+    fn drop(&mut self) {
+        for &(name, ref value) in &self.previous {
+            match *value {
+                Some(ref value) => env::set_var(name, value),
+                None => env::remove_var(name),
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The directory for an executable temporary is on a file system mounted `noexec`, so the file
+/// could be created but not run.
+///
+/// It is returned as the inner error of an `io::Error` of kind `PermissionDenied`.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::{Builder, NoExecError};
+///
+/// match Builder::new().executable_file() {
+///     Ok(script) => println!("writing {}", script.display()),
+///     Err(e) => match e.get_ref().and_then(|e| e.downcast_ref::<NoExecError>()) {
+///         Some(noexec) => eprintln!("{} is mounted noexec", noexec.dir().display()),
+///         None => eprintln!("{}", e),
+///     },
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoExecError {
+    dir: PathBuf,
+}
+
+impl NoExecError {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        NoExecError { dir }
+    }
+
+    /// The directory on the `noexec` file system.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl fmt::Display for NoExecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} is on a file system mounted noexec, files in it can't be executed",
+            self.dir.display()
+        )
+    }
+}
+
+impl Error for NoExecError {}
+
+impl From<NoExecError> for io::Error {
+    fn from(e: NoExecError) -> Self {
+        io::Error::new(io::ErrorKind::PermissionDenied, e)
+    }
+}
+
+/// The directory a temporary was to be created in is on a read-only file system, e.g. the root
+/// file system of a container with `readOnlyRootFilesystem`.
+///
+/// It is returned as the inner error of an `io::Error` of kind `PermissionDenied`. With
+/// [`Builder::retry_when_full`](struct.Builder.html#method.retry_when_full) the next usable
+/// root is tried first.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::{ReadOnlyError, Temp};
+///
+/// match Temp::new_file() {
+///     Ok(temp_file) => println!("writing {}", temp_file.display()),
+///     Err(e) => match e.get_ref().and_then(|e| e.downcast_ref::<ReadOnlyError>()) {
+///         Some(read_only) => eprintln!("{} is read-only", read_only.dir().display()),
+///         None => eprintln!("{}", e),
+///     },
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadOnlyError {
+    dir: PathBuf,
+}
+
+impl ReadOnlyError {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        ReadOnlyError { dir }
+    }
+
+    /// The directory on the read-only file system.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl fmt::Display for ReadOnlyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} is on a read-only file system, point TMPDIR or mktemp::set_roots at a writable \
+             directory",
+            self.dir.display()
+        )
+    }
+}
+
+impl Error for ReadOnlyError {}
+
+impl From<ReadOnlyError> for io::Error {
+    fn from(e: ReadOnlyError) -> Self {
+        io::Error::new(io::ErrorKind::PermissionDenied, e)
+    }
+}
@@ -0,0 +1,167 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! A small C ABI over [`Temp`](../struct.Temp.html), enabled with the `ffi` feature.
+//!
+//! Build a shared library with:
+//!
+//! ```text
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! The matching declarations live in `include/mktemp.h`.
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use Temp;
+
+/// An owned temporary file or directory handed out across the C boundary.
+pub struct MktempTemp {
+    temp: Temp,
+    c_path: CString,
+}
+
+fn into_raw(temp: Temp) -> *mut MktempTemp {
+    match c_path(&temp) {
+        Some(c_path) => Box::into_raw(Box::new(MktempTemp { temp, c_path })),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Run `f`, stopping a panic from unwinding into C, which is undefined behavior. The panic
+/// message has already been printed by the panic hook.
+fn no_unwind<T, F: FnOnce() -> T>(f: F, on_panic: T) -> T {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(on_panic)
+}
+
+#[cfg(unix)]
+fn c_path(path: &Path) -> Option<CString> {
+    use std::os::unix::ffi::OsStrExt;
+
+    CString::new(path.as_os_str().as_bytes()).ok()
+}
+
+#[cfg(not(unix))]
+fn c_path(path: &Path) -> Option<CString> {
+    path.to_str().and_then(|path| CString::new(path).ok())
+}
+
+/// Create a temporary file, returning NULL on failure, including a panic.
+#[no_mangle]
+pub extern "C" fn mktemp_new_file() -> *mut MktempTemp {
+    no_unwind(
+        || Temp::new_file().map(into_raw).unwrap_or(ptr::null_mut()),
+        ptr::null_mut(),
+    )
+}
+
+/// Create a temporary directory, returning NULL on failure, including a panic.
+#[no_mangle]
+pub extern "C" fn mktemp_new_dir() -> *mut MktempTemp {
+    no_unwind(
+        || Temp::new_dir().map(into_raw).unwrap_or(ptr::null_mut()),
+        ptr::null_mut(),
+    )
+}
+
+/// Borrow the path of a temporary as a NUL terminated string.
+///
+/// The returned pointer is valid until the handle is passed to `mktemp_release` or
+/// `mktemp_free`.
+///
+/// # Safety
+///
+/// `temp` must be NULL or a live handle returned by this library.
+#[no_mangle]
+pub unsafe extern "C" fn mktemp_path(temp: *const MktempTemp) -> *const c_char {
+    match temp.as_ref() {
+        Some(temp) => temp.c_path.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Free the handle without removing the file or directory from the file system.
+///
+/// # Safety
+///
+/// `temp` must be NULL or a live handle returned by this library, and must not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn mktemp_release(temp: *mut MktempTemp) {
+    if !temp.is_null() {
+        let temp = Box::from_raw(temp);
+        no_unwind(
+            move || {
+                temp.temp.release();
+            },
+            (),
+        );
+    }
+}
+
+/// Free the handle and remove the file or directory from the file system. A panic while
+/// removing it, e.g. in strict mode, is not propagated.
+///
+/// # Safety
+///
+/// `temp` must be NULL or a live handle returned by this library, and must not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn mktemp_free(temp: *mut MktempTemp) {
+    if !temp.is_null() {
+        let temp = Box::from_raw(temp);
+        no_unwind(move || drop(temp), ());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+    use std::path::PathBuf;
+
+    fn path_of(temp: *const MktempTemp) -> PathBuf {
+        let path = unsafe { CStr::from_ptr(mktemp_path(temp)) };
+        PathBuf::from(path.to_str().unwrap())
+    }
+
+    #[test]
+    fn free_removes_file() {
+        let temp = mktemp_new_file();
+        assert!(!temp.is_null());
+
+        let path = path_of(temp);
+        assert!(path.is_file());
+
+        unsafe { mktemp_free(temp) };
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn release_keeps_dir() {
+        let temp = mktemp_new_dir();
+        assert!(!temp.is_null());
+
+        let path = path_of(temp);
+        unsafe { mktemp_release(temp) };
+        assert!(path.is_dir());
+
+        std::fs::remove_dir(path).unwrap();
+    }
+
+    #[test]
+    fn null_handles_are_ignored() {
+        unsafe {
+            assert!(mktemp_path(ptr::null()).is_null());
+            mktemp_release(ptr::null_mut());
+            mktemp_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn panics_are_caught() {
+        let result: *mut MktempTemp = no_unwind(|| panic!("creation failed"), ptr::null_mut());
+        assert!(result.is_null());
+    }
+}
@@ -0,0 +1,171 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+#[cfg(unix)]
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsHandle, AsRawHandle, BorrowedHandle, OwnedHandle, RawHandle};
+use std::path::Path;
+
+use {Builder, Temp};
+
+/// A temporary file together with an open handle to it, created with
+/// [`Builder::open_file`](struct.Builder.html#method.open_file). The handle is closed before
+/// the file is removed.
+///
+/// The handle can be shared with child processes, or sent over a Unix socket with
+/// `SCM_RIGHTS`, through [`as_fd`](#method.as_fd) or a duplicate from
+/// [`try_clone_fd`](#method.try_clone_fd), while the guard stays responsible for removing the
+/// file.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::TempFile;
+/// use std::io::{Read, Seek, SeekFrom, Write};
+///
+/// let mut file = TempFile::new().unwrap();
+/// file.write_all(b"scratch").unwrap();
+/// file.seek(SeekFrom::Start(0)).unwrap();
+///
+/// let mut contents = String::new();
+/// file.read_to_string(&mut contents).unwrap();
+/// assert_eq!(contents, "scratch");
+/// ```
+#[derive(Debug)]
+pub struct TempFile {
+    // Declared first so it is closed before the file is removed, which Windows requires.
+    file: File,
+    temp: Temp,
+}
+
+impl TempFile {
+    /// Create a temporary file in the system temp directory and open it for reading and
+    /// writing.
+    #[track_caller]
+    pub fn new() -> io::Result<Self> {
+        Builder::new().open_file()
+    }
+
+    pub(crate) fn from_parts(file: File, temp: Temp) -> Self {
+        TempFile { file, temp }
+    }
+
+    /// The path of the file.
+    pub fn path(&self) -> &Path {
+        self.temp.path()
+    }
+
+    /// The open handle.
+    pub fn as_file(&self) -> &File {
+        &self.file
+    }
+
+    /// The open handle, mutably.
+    pub fn as_file_mut(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    /// The guard, to use the methods of [`Temp`](struct.Temp.html).
+    pub fn as_temp(&self) -> &Temp {
+        &self.temp
+    }
+
+    /// Split into the handle and the guard.
+    pub fn into_parts(self) -> (File, Temp) {
+        (self.file, self.temp)
+    }
+
+    /// A duplicate of the file descriptor, e.g. to hand to a child process, which can outlive
+    /// this guard without keeping the file from being removed.
+    #[cfg(unix)]
+    pub fn try_clone_fd(&self) -> io::Result<OwnedFd> {
+        self.file.as_fd().try_clone_to_owned()
+    }
+
+    /// A duplicate of the file handle, e.g. to hand to a child process, see
+    /// [`try_clone_fd`](#method.try_clone_fd).
+    #[cfg(windows)]
+    pub fn try_clone_handle(&self) -> io::Result<OwnedHandle> {
+        self.file.as_handle().try_clone_to_owned()
+    }
+}
+
+impl AsRef<Path> for TempFile {
+    fn as_ref(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl Read for TempFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for TempFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for TempFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for TempFile {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+#[cfg(unix)]
+impl AsFd for TempFile {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+#[cfg(windows)]
+impl AsRawHandle for TempFile {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.file.as_raw_handle()
+    }
+}
+
+#[cfg(windows)]
+impl AsHandle for TempFile {
+    fn as_handle(&self) -> BorrowedHandle<'_> {
+        self.file.as_handle()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cloned_fd_outlives_guard() {
+        let mut file = TempFile::new().unwrap();
+        file.write_all(b"shared").unwrap();
+        let path = file.path().to_path_buf();
+
+        let mut clone = File::from(file.try_clone_fd().unwrap());
+        drop(file);
+        assert!(!path.exists());
+
+        let mut contents = String::new();
+        clone.seek(SeekFrom::Start(0)).unwrap();
+        clone.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "shared");
+    }
+}
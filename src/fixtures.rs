@@ -0,0 +1,234 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! Throwaway project fixtures for integration tests, enabled with the `fixtures` feature.
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use Temp;
+
+/// Run `program` with `args` in `dir`, failing with its error output if it doesn't succeed.
+fn run(program: &str, args: &[&str], dir: &Path) -> io::Result<()> {
+    let output = Command::new(program).args(args).current_dir(dir).output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        format!(
+            "`{} {}` failed with {}: {}",
+            program,
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    ))
+}
+
+/// Create a temporary directory initialized as a git repository, using the `git` command.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mktemp::GitRepo;
+///
+/// let repo = GitRepo::new().initial_commit(true).create().unwrap();
+/// assert!(repo.join(".git").is_dir());
+/// ```
+#[derive(Debug, Clone)]
+pub struct GitRepo {
+    user_name: String,
+    user_email: String,
+    initial_commit: bool,
+}
+
+impl Default for GitRepo {
+    fn default() -> Self {
+        GitRepo {
+            user_name: "mktemp".to_owned(),
+            user_email: "mktemp@localhost".to_owned(),
+            initial_commit: false,
+        }
+    }
+}
+
+impl GitRepo {
+    /// A repository without commits, committing as `mktemp <mktemp@localhost>`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the author and committer for commits in the repository, stored in its local config so
+    /// the tests don't depend on the global git config.
+    pub fn user(&mut self, name: &str, email: &str) -> &mut Self {
+        self.user_name = name.to_owned();
+        self.user_email = email.to_owned();
+        self
+    }
+
+    /// Start the repository with an empty commit, so `HEAD` exists.
+    pub fn initial_commit(&mut self, initial_commit: bool) -> &mut Self {
+        self.initial_commit = initial_commit;
+        self
+    }
+
+    /// Create the repository.
+    #[track_caller]
+    pub fn create(&self) -> io::Result<Temp> {
+        let repo = Temp::new_dir()?;
+        run("git", &["init", "--quiet"], &repo)?;
+        run("git", &["config", "user.name", &self.user_name], &repo)?;
+        run("git", &["config", "user.email", &self.user_email], &repo)?;
+        if self.initial_commit {
+            run(
+                "git",
+                &[
+                    "commit",
+                    "--quiet",
+                    "--allow-empty",
+                    "--no-gpg-sign",
+                    "--message",
+                    "Initial commit",
+                ],
+                &repo,
+            )?;
+        }
+        Ok(repo)
+    }
+}
+
+/// Create a temporary directory holding a minimal cargo package, for tools that run `cargo` in
+/// their integration tests.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::CargoProject;
+///
+/// let project = CargoProject::new()
+///     .name("greeter")
+///     .lib("pub fn greet() -> &'static str { \"hello\" }")
+///     .create()
+///     .unwrap();
+/// assert!(project.join("Cargo.toml").is_file());
+/// assert!(project.join("src").join("lib.rs").is_file());
+/// ```
+#[derive(Debug, Clone)]
+pub struct CargoProject {
+    name: String,
+    edition: String,
+    lib: bool,
+    source: String,
+}
+
+impl Default for CargoProject {
+    fn default() -> Self {
+        CargoProject {
+            name: "fixture".to_owned(),
+            edition: "2021".to_owned(),
+            lib: false,
+            source: "fn main() {}\n".to_owned(),
+        }
+    }
+}
+
+impl CargoProject {
+    /// A binary package named `fixture` with an empty `main`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the package name.
+    pub fn name(&mut self, name: &str) -> &mut Self {
+        self.name = name.to_owned();
+        self
+    }
+
+    /// Set the Rust edition, `"2021"` by default.
+    pub fn edition(&mut self, edition: &str) -> &mut Self {
+        self.edition = edition.to_owned();
+        self
+    }
+
+    /// Make it a binary package with `source` as `src/main.rs`.
+    pub fn main(&mut self, source: &str) -> &mut Self {
+        self.lib = false;
+        self.source = source.to_owned();
+        self
+    }
+
+    /// Make it a library package with `source` as `src/lib.rs`.
+    pub fn lib(&mut self, source: &str) -> &mut Self {
+        self.lib = true;
+        self.source = source.to_owned();
+        self
+    }
+
+    /// Create the package.
+    #[track_caller]
+    pub fn create(&self) -> io::Result<Temp> {
+        let project = Temp::new_dir()?;
+        fs::write(
+            project.join("Cargo.toml"),
+            format!(
+                "[package]\nname = {:?}\nversion = \"0.1.0\"\nedition = {:?}\n\n[dependencies]\n",
+                self.name, self.edition
+            ),
+        )?;
+        let src = project.join("src");
+        fs::create_dir(&src)?;
+        let file = if self.lib { "lib.rs" } else { "main.rs" };
+        fs::write(src.join(file), &self.source)?;
+        Ok(project)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has(program: &str) -> bool {
+        Command::new(program).arg("--version").output().is_ok()
+    }
+
+    #[test]
+    fn cargo_project_layout() {
+        let project = CargoProject::new()
+            .name("tool")
+            .main("fn main() { println!(\"hi\"); }")
+            .create()
+            .unwrap();
+
+        let manifest = fs::read_to_string(project.join("Cargo.toml")).unwrap();
+        assert!(manifest.starts_with("[package]\nname = \"tool\"\n"));
+        assert_eq!(
+            fs::read_to_string(project.join("src").join("main.rs")).unwrap(),
+            "fn main() { println!(\"hi\"); }"
+        );
+        assert!(!project.join("src").join("lib.rs").exists());
+    }
+
+    #[test]
+    fn git_repo_with_initial_commit() {
+        if !has("git") {
+            return;
+        }
+
+        let repo = GitRepo::new()
+            .user("Fixture", "fixture@example.com")
+            .initial_commit(true)
+            .create()
+            .unwrap();
+        let output = Command::new("git")
+            .args(["log", "--format=%an <%ae> %s"])
+            .current_dir(&repo)
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap().trim(),
+            "Fixture <fixture@example.com> Initial commit"
+        );
+    }
+}
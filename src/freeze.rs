@@ -0,0 +1,160 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+use path_error::annotate;
+use sys;
+use Temp;
+
+impl Temp {
+    /// Create a temporary directory holding a read-only copy of the directory tree at `src`,
+    /// see [`dir_from`](#method.dir_from) and [`freeze`](#method.freeze), for tests that check
+    /// the code under test doesn't modify its inputs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    ///
+    /// let fixture = Temp::new_dir().unwrap();
+    /// fs::write(fixture.join("input.txt"), "data").unwrap();
+    ///
+    /// let input = Temp::snapshot_of(&fixture).unwrap();
+    /// let metadata = fs::metadata(input.join("input.txt")).unwrap();
+    /// assert!(metadata.permissions().readonly());
+    /// ```
+    #[track_caller]
+    pub fn snapshot_of<P: AsRef<Path>>(src: P) -> io::Result<Temp> {
+        let temp = Temp::dir_from(src)?;
+        temp.freeze()?;
+        Ok(temp)
+    }
+
+    /// Make the temporary and, for a directory, everything in it read-only, without following
+    /// symlinks. Write permission is restored before the temporary is removed, so it can still
+    /// be cleaned up. Processes running as root or with `CAP_DAC_OVERRIDE` can still write to
+    /// it.
+    pub fn freeze(&self) -> io::Result<()> {
+        self.frozen.store(true, Ordering::Relaxed);
+        set_tree_readonly(&self.path, true)
+    }
+
+    /// Make a [frozen](#method.freeze) temporary writable again.
+    pub fn thaw(&self) -> io::Result<()> {
+        set_tree_readonly(&self.path, false)?;
+        self.frozen.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Set the read-only flag of `path` and everything in it, directories last when making them
+/// read-only and first when making them writable, so their entries can always be changed.
+pub(crate) fn set_tree_readonly(path: &Path, readonly: bool) -> io::Result<()> {
+    let metadata = annotate(fs::symlink_metadata(path), "read metadata of", path)?;
+    if metadata.file_type().is_symlink() {
+        return Ok(());
+    }
+
+    let mut permissions = metadata.permissions();
+    sys::set_readonly(&mut permissions, readonly);
+    let set = || {
+        annotate(
+            fs::set_permissions(path, permissions.clone()),
+            "set permissions of",
+            path,
+        )
+    };
+    if !metadata.is_dir() {
+        return set();
+    }
+
+    if !readonly {
+        set()?;
+    }
+    for entry in annotate(fs::read_dir(path), "read directory", path)? {
+        let entry = annotate(entry, "read directory", path)?;
+        set_tree_readonly(&entry.path(), readonly)?;
+    }
+    if readonly {
+        set()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frozen_dir_is_removed() {
+        let temp = Temp::new_dir().unwrap();
+        fs::create_dir(temp.join("sub")).unwrap();
+        fs::write(temp.join("sub").join("file"), "data").unwrap();
+        temp.freeze().unwrap();
+
+        let file = temp.join("sub").join("file");
+        assert!(fs::metadata(&file).unwrap().permissions().readonly());
+        assert!(fs::metadata(temp.join("sub"))
+            .unwrap()
+            .permissions()
+            .readonly());
+
+        let path = temp.to_path_buf();
+        temp.close().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn thawed_dir_is_writable() {
+        let temp = Temp::new_dir().unwrap();
+        fs::write(temp.join("file"), "data").unwrap();
+        temp.freeze().unwrap();
+        temp.thaw().unwrap();
+        fs::write(temp.join("file"), "changed").unwrap();
+        fs::write(temp.join("new"), "").unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn snapshot_of_read_only_fixture() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let fixture = Temp::new_dir().unwrap();
+        fs::create_dir(fixture.join("inputs")).unwrap();
+        fs::write(fixture.join("inputs").join("input.txt"), "data").unwrap();
+        fs::set_permissions(fixture.join("inputs"), fs::Permissions::from_mode(0o555)).unwrap();
+
+        let snapshot = Temp::snapshot_of(&fixture).unwrap();
+        let input = snapshot.join("inputs").join("input.txt");
+        assert_eq!(fs::read_to_string(&input).unwrap(), "data");
+        assert!(fs::metadata(&input).unwrap().permissions().readonly());
+
+        let path = snapshot.to_path_buf();
+        snapshot.close().unwrap();
+        assert!(!path.exists());
+        // Writable again, so the fixture can be removed without root.
+        fs::set_permissions(fixture.join("inputs"), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn thaw_keeps_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = Temp::new_dir().unwrap();
+        fs::write(temp.join("file"), "data").unwrap();
+        fs::set_permissions(temp.join("file"), fs::Permissions::from_mode(0o600)).unwrap();
+        fs::set_permissions(&temp, fs::Permissions::from_mode(0o700)).unwrap();
+        temp.freeze().unwrap();
+        temp.thaw().unwrap();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&temp.join("file")), 0o600);
+        assert_eq!(mode(&temp), 0o700);
+    }
+}
@@ -0,0 +1,116 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fmt;
+use std::panic::Location;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+use registry::{self, Site};
+
+/// A temporary that still exists although it was neither released nor kept alive, see
+/// [`leak_check`](fn.leak_check.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leak {
+    path: PathBuf,
+    creation_site: Site,
+    label: Option<Arc<str>>,
+}
+
+impl Leak {
+    /// The path of the leaked temporary.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Where the temporary was created.
+    pub fn creation_site(&self) -> Option<&'static Location<'static>> {
+        self.creation_site
+    }
+
+    /// The label given with [`Builder::label`](struct.Builder.html#method.label).
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+}
+
+impl fmt::Display for Leak {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(ref label) = self.label {
+            write!(f, " ({})", label)?;
+        }
+        match self.creation_site {
+            Some(site) => write!(f, " created at {}", site),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Records every temporary created while it is alive and panics when it is dropped if any
+/// of them still exist without having been released, so a test suite that leaks scratch
+/// files fails. See [`leak_check`](fn.leak_check.html).
+#[derive(Debug)]
+pub struct LeakCheck {
+    _private: (),
+}
+
+impl LeakCheck {
+    /// The temporaries that have leaked so far: those that still exist on disk although they
+    /// weren't released, either because their guard is still alive or because removing them
+    /// failed.
+    pub fn leaks(&self) -> Vec<Leak> {
+        let mut leaks: Vec<Leak> = registry::snapshot()
+            .into_iter()
+            .filter(|entry| entry.0.symlink_metadata().is_ok())
+            .map(|(path, record)| Leak {
+                path,
+                creation_site: record.site,
+                label: record.label,
+            })
+            .collect();
+        leaks.sort_by(|a, b| a.path.cmp(&b.path));
+        leaks
+    }
+}
+
+impl Drop for LeakCheck {
+    fn drop(&mut self) {
+        let leaks = self.leaks();
+        registry::disable();
+
+        if !leaks.is_empty() && !thread::panicking() {
+            let list: Vec<String> = leaks.iter().map(|leak| format!("  {}", leak)).collect();
+            panic!("{} leaked temporaries:\n{}", leaks.len(), list.join("\n"));
+        }
+    }
+}
+
+/// Start recording the temporaries created by the whole process, to check for leaks when the
+/// returned guard is dropped. Create it at the start of a test `main`, or of a test that runs
+/// alone, and keep it until the end.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::Temp;
+///
+/// let check = mktemp::leak_check();
+///
+/// let released = Temp::new_file().unwrap().release();
+/// let alive = Temp::new_file().unwrap();
+/// drop(Temp::new_dir().unwrap());
+///
+/// let leaks = check.leaks();
+/// assert_eq!(leaks.len(), 1);
+/// assert_eq!(leaks[0].path(), alive.path());
+///
+/// drop(alive);
+/// drop(check);
+/// # std::fs::remove_file(released).unwrap();
+/// ```
+pub fn leak_check() -> LeakCheck {
+    registry::enable();
+    LeakCheck { _private: () }
+}
@@ -19,88 +19,451 @@
 //! // temp_file is cleaned from the fs here
 //! ```
 //!
+#[cfg(feature = "arbitrary")]
+extern crate arbitrary;
+#[cfg(feature = "assert_fs")]
+extern crate assert_fs_crate;
+#[cfg(feature = "archive")]
+extern crate flate2;
+#[cfg(feature = "async")]
+extern crate futures_core;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "notify")]
+pub extern crate notify;
+#[cfg(feature = "assert_fs")]
+extern crate predicates_core;
+#[cfg(feature = "serde")]
+extern crate serde_crate;
+#[cfg(any(feature = "serde", feature = "audit"))]
+extern crate serde_json;
+#[cfg(feature = "serde")]
+extern crate serde_yaml;
+#[cfg(feature = "archive")]
+extern crate tar;
+#[cfg(any(feature = "upload", feature = "async"))]
+extern crate tokio;
+#[cfg(feature = "serde")]
+extern crate toml;
+#[cfg(feature = "trash")]
+extern crate trash;
 extern crate uuid;
 
-use std::env;
+use std::ffi::OsStr;
+use std::fmt;
 use std::fs;
 use std::io;
 use std::ops;
-#[cfg(unix)]
-use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::panic::Location;
 use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
-#[derive(Debug)]
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impls;
+#[cfg(feature = "archive")]
+mod archive;
+#[cfg(feature = "assert_fs")]
+mod assert_fs_impls;
+mod atomic;
+#[cfg(feature = "audit")]
+mod audit;
+pub mod backend;
+mod builder;
+mod cache;
+mod cleanup;
+mod command;
+mod config;
+mod diagnostics;
+#[cfg(feature = "async")]
+mod entries_stream;
+mod env;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod file;
+#[cfg(feature = "fixtures")]
+mod fixtures;
+mod freeze;
+mod leak;
+mod limit;
+mod log;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod memfd;
+mod open;
+mod path_error;
+mod pool;
+mod process_dir;
+mod registry;
+mod relocate;
+mod root;
+mod sandbox;
+mod sanitize;
+mod scope;
+#[cfg(feature = "serde")]
+mod serialize;
+mod session;
+mod shared;
+mod snapshot;
+mod spill;
+mod sys;
+mod templog;
+mod token;
+mod tombstone;
+mod typed;
+#[cfg(feature = "upload")]
+mod upload;
+mod usage;
+#[cfg(feature = "notify")]
+mod watch;
+mod watchdog;
+
+pub use atomic::{atomic_write, AtomicWriter};
+#[cfg(feature = "audit")]
+pub use audit::set_audit_sink;
+use backend::{DryRunFs, OsFs, TempFs};
+pub use builder::Builder;
+pub use cache::TempCache;
+pub use cleanup::{CancelToken, CleanupHandle};
+pub use config::{config, configure, CleanupPolicy, Config, Naming};
+pub use diagnostics::{diagnostics, Diagnostics, RootSource};
+#[cfg(feature = "async")]
+pub use entries_stream::{EntriesStream, NextEntry};
+pub use env::TempEnv;
+pub use error::{NoExecError, ReadOnlyError};
+pub use file::TempFile;
+#[cfg(feature = "fixtures")]
+pub use fixtures::{CargoProject, GitRepo};
+pub use leak::{leak_check, Leak, LeakCheck};
+pub use limit::{set_creation_limit, CreationLimit, OnLimit};
+pub use log::set_log_hook;
+pub use open::{advise, Advice};
+use path_error::annotate;
+pub use pool::{PooledFile, TempPool};
+pub use process_dir::process_dir;
+pub use root::{
+    root, set_local_fallback, set_roots, set_secure_system_temp, set_symlink_free_root, with_root,
+    RunDir,
+};
+pub use sandbox::{Sandbox, SandboxOutput};
+pub use scope::TempScope;
+pub use session::Session;
+pub use shared::{SharedTemp, WeakTemp};
+pub use snapshot::{TreeDiff, TreeSnapshot};
+pub use spill::SpillBuffer;
+pub use templog::TempLog;
+pub use token::TempToken;
+pub use tombstone::set_tombstones;
+pub use typed::{TempDir, TempPath};
+#[cfg(feature = "upload")]
+pub use upload::{Spool, TooLargeError};
+pub use usage::{track_usage, usage_by_label, usage_report, LabelUsage, PlannedRemoval, SiteUsage};
+#[cfg(feature = "notify")]
+pub use watch::Watch;
+pub use watchdog::Watchdog;
+
 pub struct Temp {
     path: PathBuf,
+    kind: Kind,
+    fs: Option<Arc<dyn TempFs>>,
+    strict: Option<bool>,
+    sync_on_release: bool,
+    delete_on_reboot: bool,
+    // Dropped after the temporary is removed, so a parent directory outlives its children.
+    parent: Option<SharedTemp>,
+    cleanup_deadline: Option<Duration>,
+    crash_artifacts: Option<PathBuf>,
+    armed: bool,
+    expect_empty: bool,
+    frozen: AtomicBool,
+    label: Option<Arc<str>>,
+    created_at: SystemTime,
+    cleanup_hooks: Mutex<Vec<CleanupHook>>,
+    // Dropped after the temporary is removed, `None` for background copies.
+    live: Option<limit::Live>,
+    #[cfg(debug_assertions)]
+    creation_site: &'static Location<'static>,
 }
 
-fn create_path() -> PathBuf {
-    create_path_in(env::temp_dir())
+type CleanupHook = Box<dyn FnOnce(&Path) + Send>;
+
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+/// Turn strict mode on or off for every temporary that doesn't choose for itself with
+/// [`Builder::strict`](struct.Builder.html#method.strict).
+///
+/// In strict mode a temporary that fails to clean itself up when dropped panics in debug builds,
+/// so test suites notice leaked files instead of silently filling the disk. Release builds are
+/// unaffected.
+pub fn set_strict(strict: bool) {
+    STRICT.store(strict, Ordering::Relaxed);
 }
 
-fn create_path_in(path: PathBuf) -> PathBuf {
-    let mut path = path;
-    let dir_uuid = Uuid::new_v4();
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+static DRY_RUN_FS: DryRunFs = DryRunFs::new();
+
+/// Turn dry-run mode on or off for every temporary that doesn't use its own
+/// [`Builder::fs`](struct.Builder.html#method.fs) backend.
+///
+/// In dry-run mode temporaries are created as usual, but cleanup only records the paths that
+/// would have been removed, see [`dry_run_report`](fn.dry_run_report.html). This is for
+/// auditing exactly what an application removes before trusting it in production.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::Temp;
+///
+/// mktemp::set_dry_run(true);
+/// let path = Temp::new_file().unwrap().to_path_buf();
+///
+/// assert!(path.exists());
+/// assert_eq!(mktemp::dry_run_report(), vec![path.clone()]);
+/// assert!(mktemp::dry_run_report().is_empty());
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+/// The paths that would have been removed in dry-run mode since the last call, see
+/// [`set_dry_run`](fn.set_dry_run.html). They are forgotten once reported, so a long-running
+/// process doesn't keep every path it ever created.
+pub fn dry_run_report() -> Vec<PathBuf> {
+    DRY_RUN_FS.take_report()
+}
+
+/// The kind of file system resource a temporary refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// A regular file.
+    File,
+    /// A directory.
+    Dir,
+    /// A path that isn't created automatically.
+    Path,
+}
+
+static NAME_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A unique name following the crate's conventions: the prefix set with
+/// [`configure`](fn.configure.html), the [`Naming`](enum.Naming.html) scheme and a random UUID.
+/// For resources that aren't files but need collision-resistant names, such as shared memory
+/// segments or message queues.
+///
+/// # Examples
+///
+/// ```
+/// assert_ne!(mktemp::unique_name(), mktemp::unique_name());
+/// ```
+pub fn unique_name() -> String {
+    compose_name(&config::default_prefix(), "")
+}
+
+/// Like [`unique_name`](fn.unique_name.html), with `prefix` instead of the configured one and
+/// `suffix` appended.
+///
+/// # Examples
+///
+/// ```
+/// let queue = mktemp::unique_name_with("/jobs-", ".mq");
+/// assert!(queue.starts_with("/jobs-") && queue.ends_with(".mq"));
+/// ```
+pub fn unique_name_with(prefix: &str, suffix: &str) -> String {
+    compose_name(prefix, suffix)
+}
+
+fn compose_name(prefix: &str, suffix: &str) -> String {
+    compose_name_with_entropy(prefix, suffix, None)
+}
+
+/// The name with a random part of at least `entropy_bits` bits, or a UUID if `None`.
+fn compose_name_with_entropy(prefix: &str, suffix: &str, entropy_bits: Option<u32>) -> String {
+    let mut uuid = [0u8; uuid::fmt::Simple::LENGTH];
+    let compact;
+    let random: &str = match entropy_bits {
+        Some(bits) => {
+            compact = random_chars(bits);
+            &compact
+        }
+        None => Uuid::new_v4().simple().encode_lower(&mut uuid),
+    };
+    let stamp = match config::naming() {
+        Naming::Random => String::new(),
+        Naming::Timestamped => {
+            let since_epoch = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            format!("{}-", since_epoch.as_secs())
+        }
+        Naming::HostUnique => {
+            let count = NAME_COUNTER.fetch_add(1, Ordering::Relaxed);
+            format!("{}-{}-{}-", sys::hostname(), process::id(), count)
+        }
+    };
+
+    // Compose the name once with its exact size.
+    let mut name = String::with_capacity(prefix.len() + stamp.len() + random.len() + suffix.len());
+    name.push_str(prefix);
+    name.push_str(&stamp);
+    name.push_str(random);
+    name.push_str(suffix);
+    name
+}
 
-    path.push(dir_uuid.simple().to_string());
+/// Lowercase letters and digits, so names stay distinct on case-insensitive file systems, 5 bits
+/// per character.
+const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+const BITS_PER_CHAR: u32 = 5;
+
+/// The fewest random bits a name can be configured with, see
+/// [`Builder::min_entropy_bits`](struct.Builder.html#method.min_entropy_bits).
+pub(crate) const MIN_ENTROPY_BITS: u32 = 64;
+
+/// Enough random characters from `ALPHABET` for `bits` bits of entropy.
+fn random_chars(bits: u32) -> String {
+    let len = ((bits + BITS_PER_CHAR - 1) / BITS_PER_CHAR) as usize;
+    let mut chars = String::with_capacity(len);
+    let (mut pool, mut available) = (0u128, 0);
+    while chars.len() < len {
+        if available < BITS_PER_CHAR {
+            pool = uuid_random_bits();
+            available = 122;
+        }
+        chars.push(ALPHABET[(pool & 0x1f) as usize] as char);
+        pool >>= BITS_PER_CHAR;
+        available -= BITS_PER_CHAR;
+    }
+    chars
+}
+
+/// The 122 random bits of a version 4 UUID, without its fixed version and variant bits.
+fn uuid_random_bits() -> u128 {
+    let uuid = Uuid::new_v4().as_u128();
+    (uuid >> 80) << 74 | ((uuid >> 64) & 0xfff) << 62 | (uuid & ((1 << 62) - 1))
+}
+
+/// The names of the entries in the directory at `path`, sorted.
+fn entry_names(path: &Path) -> io::Result<Vec<std::ffi::OsString>> {
+    let mut names = Vec::new();
+    for entry in annotate(fs::read_dir(path), "read directory", path)? {
+        names.push(annotate(entry, "read directory", path)?.file_name());
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn create_path_with_ext_in(path: PathBuf, prefix: &str, extension: Option<&str>) -> PathBuf {
+    create_path_in(path, prefix, extension, None)
+}
+
+fn create_path_in(
+    path: PathBuf,
+    prefix: &str,
+    extension: Option<&str>,
+    entropy_bits: Option<u32>,
+) -> PathBuf {
+    tombstone::check(&path);
+    // Append the extension to the name rather than using `set_extension`, which would replace
+    // everything after a dot in the prefix, random part included.
+    let name = match extension {
+        Some(extension) => {
+            compose_name_with_entropy(prefix, &format!(".{}", extension), entropy_bits)
+        }
+        None => compose_name_with_entropy(prefix, "", entropy_bits),
+    };
+
+    // Make room for the name (and the separator) in `path` up front so `push` doesn't have to
+    // reallocate.
+    let mut path = path;
+    path.reserve_exact(1 + name.len());
+    path.push(name);
     path
 }
 
 impl Temp {
     /// Create a temporary directory.
+    #[track_caller]
     pub fn new_dir() -> io::Result<Self> {
-        let path = create_path();
-        Self::create_dir(&path)?;
-
-        let temp = Temp { path };
-
-        Ok(temp)
+        Builder::new().dir()
     }
 
     /// Create a new temporary directory in an existing directory
+    #[track_caller]
     pub fn new_dir_in<P: AsRef<Path>>(directory: P) -> io::Result<Self> {
-        let path = create_path_in(directory.as_ref().to_path_buf());
-        Self::create_dir(&path)?;
-
-        let temp = Temp { path };
-
-        Ok(temp)
+        Builder::new().in_dir(directory).dir()
     }
 
     /// Create a new temporary file in an existing directory
+    #[track_caller]
     pub fn new_file_in<P: AsRef<Path>>(directory: P) -> io::Result<Self> {
-        let path = create_path_in(directory.as_ref().to_path_buf());
-        Self::create_file(&path)?;
-
-        let temp = Temp { path };
-
-        Ok(temp)
+        Builder::new().in_dir(directory).file()
     }
 
     /// Create a temporary file.
+    #[track_caller]
     pub fn new_file() -> io::Result<Self> {
-        let path = create_path();
-        Self::create_file(&path)?;
-
-        let temp = Temp { path };
+        Builder::new().file()
+    }
 
-        Ok(temp)
+    /// Create a sparse temporary file with a logical size of `len` bytes, see
+    /// [`Builder::sparse_file`](struct.Builder.html#method.sparse_file).
+    #[track_caller]
+    pub fn new_sparse_file(len: u64) -> io::Result<Self> {
+        Builder::new().sparse_file(len)
     }
 
     /// Create new uninitialized temporary path, i.e. a file or directory isn't created automatically
+    ///
+    /// # Panics
+    ///
+    /// Panics if the default directory is rejected because of
+    /// [`set_symlink_free_root`](fn.set_symlink_free_root.html), see
+    /// [`Builder::try_path`](struct.Builder.html#method.try_path).
+    #[track_caller]
     pub fn new_path() -> Self {
-        let path = create_path();
-
-        Temp { path }
+        Builder::new().path()
     }
 
     /// Create a new uninitialized temporary path in an existing directory i.e. a file or directory
     /// isn't created automatically
+    #[track_caller]
     pub fn new_path_in<P: AsRef<Path>>(directory: P) -> Self {
-        let path = create_path_in(directory.as_ref().to_path_buf());
+        Builder::new().in_dir(directory).path()
+    }
+
+    /// Create a new uninitialized temporary path ending in `.{extension}`, for programs that
+    /// create the file themselves but pick its format by the extension.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the default directory is rejected because of
+    /// [`set_symlink_free_root`](fn.set_symlink_free_root.html), see
+    /// [`Builder::try_path`](struct.Builder.html#method.try_path).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    ///
+    /// let db = Temp::new_path_with_extension("db");
+    /// assert_eq!(db.extension().unwrap(), "db");
+    /// assert!(!db.exists());
+    /// ```
+    #[track_caller]
+    pub fn new_path_with_extension(extension: &str) -> Self {
+        Builder::new().extension(extension).path()
+    }
 
-        Temp { path }
+    /// Create a new uninitialized temporary path ending in `.{extension}` in an existing
+    /// directory.
+    #[track_caller]
+    pub fn new_path_with_extension_in<P: AsRef<Path>>(directory: P, extension: &str) -> Self {
+        Builder::new().in_dir(directory).extension(extension).path()
     }
 
     /// Return this temporary file or directory as a PathBuf.
@@ -117,6 +480,136 @@ impl Temp {
         PathBuf::from(&self.path)
     }
 
+    /// The path of the temporary.
+    ///
+    /// The path can't be changed in place, since the temporary would then remove the wrong
+    /// location; use [`rename_within`](#method.rename_within) or
+    /// [`move_into`](#method.move_into) to move it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    ///
+    /// let temp_file = Temp::new_file().unwrap();
+    /// assert!(temp_file.path().is_file());
+    /// ```
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Where in the source the temporary was created, to find the code responsible for a leaked
+    /// temporary. Only recorded in debug builds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    ///
+    /// let temp_file = Temp::new_file().unwrap();
+    /// if let Some(site) = temp_file.creation_site() {
+    ///     assert_eq!(site.file(), file!());
+    /// }
+    /// ```
+    pub fn creation_site(&self) -> Option<&'static Location<'static>> {
+        #[cfg(debug_assertions)]
+        {
+            Some(self.creation_site)
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            None
+        }
+    }
+
+    /// The label given with [`Builder::label`](struct.Builder.html#method.label).
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// When the guard was created, without touching the file system. For a temporary taken over
+    /// with [`from_token`](#method.from_token), this is when it was taken over.
+    pub fn created_at(&self) -> SystemTime {
+        self.created_at
+    }
+
+    /// How long ago the guard was created, zero if the system clock has gone backwards since.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::time::Duration;
+    ///
+    /// let temp_file = Temp::new_file().unwrap();
+    /// assert!(temp_file.age() < Duration::from_secs(60));
+    /// ```
+    pub fn age(&self) -> Duration {
+        self.created_at.elapsed().unwrap_or_default()
+    }
+
+    /// The kind of resource the temporary was created as, without touching the file system.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::{Kind, Temp};
+    ///
+    /// assert_eq!(Temp::new_dir().unwrap().kind(), Kind::Dir);
+    /// assert_eq!(Temp::new_path().kind(), Kind::Path);
+    /// ```
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// The metadata of the temporary itself, not following a symlink at its path. Named after
+    /// `fs::symlink_metadata`; `Path::metadata` still follows symlinks.
+    pub fn symlink_metadata(&self) -> io::Result<fs::Metadata> {
+        annotate(
+            fs::symlink_metadata(&self.path),
+            "read metadata of",
+            &self.path,
+        )
+    }
+
+    /// Whether the temporary still exists as the [`kind`](#method.kind) it was created as.
+    ///
+    /// Something else at the path, such as a directory where the temporary file was, doesn't
+    /// count, since it isn't what will be removed. Anything counts for an uninitialized path,
+    /// and symlinks aren't followed. `Path::exists` is still available for the plain check.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    ///
+    /// let temp_file = Temp::new_file().unwrap();
+    /// assert!(temp_file.is_present());
+    ///
+    /// fs::remove_file(&temp_file).unwrap();
+    /// fs::create_dir(&temp_file).unwrap();
+    /// assert!(!temp_file.is_present());
+    /// assert!(temp_file.exists());
+    /// # fs::remove_dir(&temp_file).unwrap();
+    /// ```
+    pub fn is_present(&self) -> bool {
+        match (self.kind, fs::symlink_metadata(&self.path)) {
+            (_, Err(_)) => false,
+            (Kind::File, Ok(metadata)) => metadata.is_file(),
+            (Kind::Dir, Ok(metadata)) => metadata.is_dir(),
+            (Kind::Path, Ok(_)) => true,
+        }
+    }
+
+    /// The generated file name of the temporary, which always exists, unlike the
+    /// `Option` returned by `Path::file_name`.
+    pub fn name(&self) -> &OsStr {
+        self.path
+            .file_name()
+            .expect("temporary paths always have a file name")
+    }
+
     /// Release ownership of the temporary file or directory.
     ///
     /// # Examples
@@ -131,32 +624,513 @@ impl Temp {
     /// }
     /// assert!(path_buf.exists());
     /// ```
+    ///
+    /// If the temporary was built with
+    /// [`Builder::sync_on_release`](struct.Builder.html#method.sync_on_release) it is flushed to
+    /// disk first, ignoring errors. Call [`sync`](#method.sync) beforehand to handle them.
     pub fn release(self) -> PathBuf {
-        use std::mem::{forget, transmute_copy};
+        if self.sync_on_release {
+            let _result = self.sync();
+        }
+        self.defuse()
+    }
+
+    /// Stop the temporary from being removed when the guard is dropped, until
+    /// [`rearm`](#method.rearm) is called, for guards that can't be given up with
+    /// [`release`](#method.release) because they are only borrowed, e.g. in a struct field.
+    /// Removing it explicitly with [`close`](#method.close) still works.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    ///
+    /// struct Job {
+    ///     output: Temp,
+    /// }
+    ///
+    /// let mut job = Job {
+    ///     output: Temp::new_dir().unwrap(),
+    /// };
+    /// job.output.disarm();
+    /// let path = job.output.to_path_buf();
+    /// drop(job);
+    /// assert!(path.exists());
+    /// # std::fs::remove_dir(path).unwrap();
+    /// ```
+    pub fn disarm(&mut self) {
+        if self.armed {
+            self.armed = false;
+            registry::untrack(&self.path);
+        }
+    }
 
-        let path = unsafe { transmute_copy(&self.path) };
-        forget(self);
-        path
+    /// Remove the temporary when the guard is dropped again, after [`disarm`](#method.disarm).
+    pub fn rearm(&mut self) {
+        if !self.armed {
+            self.armed = true;
+            registry::track(&self.path, self.creation_site(), self.label.clone());
+        }
     }
 
-    fn create_file(path: &Path) -> io::Result<()> {
-        let mut builder = fs::OpenOptions::new();
-        builder.write(true).create_new(true);
+    /// Whether the temporary is removed when the guard is dropped, see
+    /// [`disarm`](#method.disarm).
+    pub fn is_armed(&self) -> bool {
+        self.armed
+    }
 
+    /// Flush the file, or the directory's entries, and its entry in the parent directory to
+    /// disk.
+    pub fn sync(&self) -> io::Result<()> {
+        let metadata = annotate(fs::metadata(&self.path), "read metadata of", &self.path)?;
+        let result = if metadata.is_file() {
+            // Windows needs write access to flush, unix doesn't, so sealed files can be synced.
+            fs::OpenOptions::new()
+                .read(true)
+                .write(cfg!(windows))
+                .open(&self.path)
+                .and_then(|file| file.sync_all())
+        } else {
+            atomic::sync_dir(&self.path)
+        };
+        annotate(result, "sync", &self.path)?;
+
+        match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => atomic::sync_dir(parent),
+            _ => Ok(()),
+        }
+    }
+
+    /// Make the temporary file read-only, catching accidental writes once it is complete. It can
+    /// still be removed when the guard is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    ///
+    /// let temp_file = Temp::new_file().unwrap();
+    /// fs::write(&temp_file, b"final").unwrap();
+    /// temp_file.seal().unwrap();
+    /// assert!(fs::metadata(&temp_file).unwrap().permissions().readonly());
+    /// ```
+    pub fn seal(&self) -> io::Result<()> {
+        self.set_readonly(true)
+    }
+
+    /// Make the temporary read-only, or writable again. On unix only the owner's write
+    /// permission is given back.
+    pub fn set_readonly(&self, readonly: bool) -> io::Result<()> {
+        let mut permissions =
+            annotate(fs::metadata(&self.path), "read metadata of", &self.path)?.permissions();
+        sys::set_readonly(&mut permissions, readonly);
+        annotate(
+            fs::set_permissions(&self.path, permissions),
+            "set permissions of",
+            &self.path,
+        )
+    }
+
+    /// Set the permission bits of the temporary, e.g. `0o644` to let other users read it.
+    ///
+    /// Windows only has a read-only attribute, which is set when `mode` doesn't let the owner
+    /// write.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    ///
+    /// let temp_file = Temp::new_file().unwrap();
+    /// temp_file.set_mode(0o444).unwrap();
+    /// assert!(fs::metadata(&temp_file).unwrap().permissions().readonly());
+    /// ```
+    pub fn set_mode(&self, mode: u32) -> io::Result<()> {
         #[cfg(unix)]
-        builder.mode(0o600);
+        {
+            use std::os::unix::fs::PermissionsExt;
 
-        builder.open(path)?;
+            annotate(
+                fs::set_permissions(&self.path, fs::Permissions::from_mode(mode)),
+                "set permissions of",
+                &self.path,
+            )
+        }
+        #[cfg(not(unix))]
+        {
+            self.set_readonly(mode & 0o200 == 0)
+        }
+    }
+
+    /// Set the modification time of the temporary to now, creating an empty file first if it is
+    /// an uninitialized path that doesn't exist yet, like `touch(1)`. For testing code that
+    /// checks whether files are fresh.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    ///
+    /// let stamp = Temp::new_path();
+    /// stamp.touch().unwrap();
+    /// assert!(stamp.is_file());
+    /// ```
+    pub fn touch(&self) -> io::Result<()> {
+        if self.kind == Kind::Path && fs::symlink_metadata(&self.path).is_err() {
+            self.fs().create_file(&self.path, 0o600)?;
+        }
+        self.set_modified(SystemTime::now())
+    }
+
+    /// Set the modification time of the temporary, e.g. to one in the past to make it look stale.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let temp_file = Temp::new_file().unwrap();
+    /// let an_hour_ago = SystemTime::now() - Duration::from_secs(60 * 60);
+    /// temp_file.set_modified(an_hour_ago).unwrap();
+    /// assert_eq!(fs::metadata(&temp_file).unwrap().modified().unwrap(), an_hour_ago);
+    /// ```
+    pub fn set_modified(&self, time: SystemTime) -> io::Result<()> {
+        annotate(
+            sys::set_modified(&self.path, time),
+            "set modification time of",
+            &self.path,
+        )
+    }
+
+    /// Give up ownership without any of the side effects of `release`.
+    fn defuse(self) -> PathBuf {
+        use std::mem::{replace, ManuallyDrop};
+
+        let mut temp = ManuallyDrop::new(self);
+        registry::untrack(&temp.path);
+        temp.fs.take();
+        temp.parent.take();
+        temp.take_cleanup_hooks();
+        temp.live.take();
+        replace(&mut temp.path, PathBuf::new())
+    }
+
+    /// Share ownership of this temporary, see [`SharedTemp`](struct.SharedTemp.html).
+    pub fn into_shared(self) -> SharedTemp {
+        SharedTemp::new(self)
+    }
+
+    /// Remove the temporary file or directory now, returning any error instead of ignoring it
+    /// like `Drop` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    ///
+    /// let temp_dir = Temp::new_dir().unwrap();
+    /// let path_buf = temp_dir.to_path_buf();
+    /// temp_dir.close().unwrap();
+    /// assert!(!path_buf.exists());
+    /// ```
+    pub fn close(mut self) -> io::Result<()> {
+        let result = self.cleanup_within_deadline();
+        if !self.armed {
+            return result;
+        }
+        self.closed(result)
+    }
+
+    /// Give up ownership once cleanup has been attempted with `result`.
+    fn closed(self, result: io::Result<()>) -> io::Result<()> {
+        let site = self.creation_site();
+        let label = self.label.clone();
+        let path = self.defuse();
+        if result.is_err() {
+            // Still on disk, so it's a leak.
+            registry::track(&path, site, label);
+        } else {
+            tombstone::bury(&path, site);
+        }
+        result
+    }
+
+    /// Point the guard at the temporary's new location after moving it, returning the old one.
+    fn moved_to(&mut self, to: PathBuf) -> PathBuf {
+        registry::moved(&self.path, &to);
+        ::std::mem::replace(&mut self.path, to)
+    }
+
+    /// Remove the temporary file or directory on a background thread, returning a handle that
+    /// can be used to wait for the removal to finish.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    ///
+    /// let temp_dir = Temp::new_dir().unwrap();
+    /// let cleanup = temp_dir.close_in_background();
+    /// // ... shut down other parts of the application ...
+    /// cleanup.wait().unwrap();
+    /// ```
+    pub fn close_in_background(self) -> CleanupHandle {
+        CleanupHandle::spawn(self)
+    }
+
+    /// Like [`close_in_background`](#method.close_in_background), but the removal stops between
+    /// entries once `token` is cancelled, e.g. by a supervisor shutting the application down.
+    /// Whatever hasn't been removed yet is left on disk, and the handle reports an `Interrupted`
+    /// error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::{CancelToken, Temp};
+    /// use std::io;
+    ///
+    /// let token = CancelToken::new();
+    /// let temp_dir = Temp::new_dir().unwrap();
+    /// let path = temp_dir.to_path_buf();
+    /// token.cancel();
+    ///
+    /// let cleanup = temp_dir.close_in_background_with(&token);
+    /// assert_eq!(cleanup.wait().unwrap_err().kind(), io::ErrorKind::Interrupted);
+    /// assert!(path.exists());
+    /// # std::fs::remove_dir(path).unwrap();
+    /// ```
+    pub fn close_in_background_with(self, token: &CancelToken) -> CleanupHandle {
+        CleanupHandle::spawn_cancellable(self, token.clone())
+    }
+
+    /// Run `hook` with the path just before the temporary is removed, when it is dropped, closed
+    /// or cleaned up in the background, e.g. to upload the artifact or record metrics first.
+    ///
+    /// Hooks run in the order they were registered, on the thread doing the removal, each one at
+    /// most once. Cleanup happens in this order:
+    ///
+    /// 1. the hooks run, while the temporary is still on disk;
+    /// 2. the temporary is removed, in the background if it takes longer than the
+    ///    [`cleanup_deadline`](struct.Builder.html#method.cleanup_deadline);
+    /// 3. the guard's reference to its parent, if any, is dropped.
+    ///
+    /// Hooks are discarded without running if the temporary is never removed: when it is
+    /// [released](#method.release), turned into a [token](#method.into_token), or kept because
+    /// the [`CleanupPolicy`](enum.CleanupPolicy.html) is `Keep`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    /// use std::sync::mpsc;
+    ///
+    /// let temp_file = Temp::new_file().unwrap();
+    /// fs::write(&temp_file, "report").unwrap();
+    ///
+    /// let (sender, receiver) = mpsc::channel();
+    /// temp_file.on_cleanup(move |path| {
+    ///     sender.send(fs::read_to_string(path).unwrap()).unwrap();
+    /// });
+    /// drop(temp_file);
+    /// assert_eq!(receiver.recv().unwrap(), "report");
+    /// ```
+    pub fn on_cleanup<F>(&self, hook: F)
+    where
+        F: FnOnce(&Path) + Send + 'static,
+    {
+        self.cleanup_hooks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Box::new(hook));
+    }
+
+    fn take_cleanup_hooks(&self) -> Vec<CleanupHook> {
+        let mut hooks = self.cleanup_hooks.lock().unwrap_or_else(|e| e.into_inner());
+        std::mem::take(&mut *hooks)
+    }
+
+    fn run_cleanup_hooks(&self) {
+        for hook in self.take_cleanup_hooks() {
+            hook(&self.path);
+        }
+    }
+
+    /// Remove the resource using the kind recorded at creation, rather than looking at whatever
+    /// currently exists at the path.
+    fn cleanup(&self) -> io::Result<()> {
+        self.run_cleanup_hooks();
+        #[cfg(feature = "audit")]
+        let removal = audit::Removal::start(&self.path);
+        let result = self.remove();
+        #[cfg(feature = "audit")]
+        {
+            if let Some(removal) = removal {
+                removal.finish(self, &result);
+            }
+        }
+        result
+    }
+
+    /// Make a frozen temporary writable again so it can be removed.
+    fn thaw_for_removal(&self) {
+        if self.frozen.load(Ordering::Relaxed) && self.fs.is_none() {
+            // Errors show up as the removal failing.
+            let _result = freeze::set_tree_readonly(&self.path, false);
+        }
+    }
+
+    /// Fail, leaving the directory in place, if it was expected to be empty but isn't.
+    fn check_expected_empty(&self) -> io::Result<()> {
+        if self.expect_empty && self.kind == Kind::Dir && self.fs.is_none() {
+            match entry_names(&self.path) {
+                Ok(ref entries) if !entries.is_empty() => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!(
+                            "{} was expected to be empty, left in place for inspection, it \
+                             contains {:?}",
+                            self.describe(),
+                            entries
+                        ),
+                    ))
+                }
+                _ => (),
+            }
+        }
         Ok(())
     }
 
-    fn create_dir(path: &Path) -> io::Result<()> {
-        let mut builder = fs::DirBuilder::new();
+    fn remove(&self) -> io::Result<()> {
+        self.check_expected_empty()?;
+        self.thaw_for_removal();
+        let result = match self.kind {
+            Kind::File => self.fs().remove_file(&self.path),
+            Kind::Dir => self.fs().remove_dir_all(&self.path),
+            Kind::Path => self.fs().remove(&self.path),
+        };
 
-        #[cfg(unix)]
-        builder.mode(0o700);
+        match result {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Remove the resource like `cleanup`, but if that takes longer than the deadline set with
+    /// [`Builder::cleanup_deadline`](struct.Builder.html#method.cleanup_deadline), stop waiting
+    /// and leave the rest to a background thread. The guard is then disarmed and a `TimedOut`
+    /// error returned, the background thread untracks the temporary once it is gone.
+    fn cleanup_within_deadline(&mut self) -> io::Result<()> {
+        self.run_cleanup_hooks();
+        let deadline = match self.cleanup_deadline {
+            Some(deadline) => deadline,
+            None => return self.cleanup(),
+        };
 
-        builder.create(path)
+        // The background copy keeps the parent alive, and counts against the limit of live
+        // temporaries, until it is done.
+        let mut background = Builder::new().guard(self.path.clone(), self.kind);
+        background.fs = self.fs.clone();
+        background.strict = self.strict;
+        background.sync_on_release = self.sync_on_release;
+        background.delete_on_reboot = self.delete_on_reboot;
+        background.parent = self.parent.clone();
+        background.expect_empty = self.expect_empty;
+        background.frozen = AtomicBool::new(self.frozen.load(Ordering::Relaxed));
+        background.label = self.label.clone();
+        background.created_at = self.created_at;
+        background.live = self.live.take();
+        #[cfg(debug_assertions)]
+        {
+            background.creation_site = self.creation_site;
+        }
+        match CleanupHandle::spawn_with(background, Temp::close_like_drop).wait_timeout(deadline) {
+            Some(result) => result,
+            None => {
+                // The background copy untracks the temporary once it is actually gone.
+                self.armed = false;
+                Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "removing {} is taking longer than {:?}, continuing in the background",
+                        self.describe(),
+                        deadline
+                    ),
+                ))
+            }
+        }
+    }
+
+    /// Close the background copy made by `cleanup_within_deadline`, which has taken the place
+    /// of the guard, so if removal fails it is left to the next reboot like `Drop` does.
+    fn close_like_drop(self) -> io::Result<()> {
+        let result = self.cleanup();
+        if result.is_err() && self.remove_on_reboot() {
+            self.defuse();
+            return result;
+        }
+        self.closed(result)
+    }
+
+    /// Have the temporary removed at the next reboot instead, if that was asked for with
+    /// [`Builder::delete_on_reboot`](struct.Builder.html#method.delete_on_reboot), returning
+    /// whether it will be.
+    fn remove_on_reboot(&self) -> bool {
+        self.delete_on_reboot && self.fs.is_none() && sys::delete_on_reboot(&self.path).is_ok()
+    }
+
+    /// The path for messages, with the label if there is one.
+    fn describe(&self) -> String {
+        match self.label {
+            Some(ref label) => format!("{} ({})", self.path.display(), label),
+            None => self.path.display().to_string(),
+        }
+    }
+
+    /// Move the resource into the crash artifacts directory, returning where it ended up.
+    fn preserve_crash_artifact(&self, dir: &Path) -> PathBuf {
+        let target = match self.path.file_name() {
+            Some(name) => dir.join(name),
+            None => return self.path.clone(),
+        };
+        match fs::create_dir_all(dir).and_then(|_| fs::rename(&self.path, &target)) {
+            Ok(()) => target,
+            Err(e) => {
+                log::warn(format_args!(
+                    "failed to move {} into {}: {}",
+                    self.describe(),
+                    dir.display(),
+                    e
+                ));
+                self.path.clone()
+            }
+        }
+    }
+
+    fn fs(&self) -> &dyn TempFs {
+        match self.fs {
+            Some(ref fs) => &**fs,
+            None if DRY_RUN.load(Ordering::Relaxed) => &DRY_RUN_FS,
+            None => &OsFs,
+        }
+    }
+}
+
+impl fmt::Debug for Temp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("Temp");
+        debug.field("path", &self.path).field("kind", &self.kind);
+        if let Some(ref label) = self.label {
+            debug.field("label", label);
+        }
+        if let Some(site) = self.creation_site() {
+            debug.field("creation_site", &format_args!("{}", site));
+        }
+        debug.finish()
     }
 }
 
@@ -166,6 +1140,36 @@ impl AsRef<Path> for Temp {
     }
 }
 
+/// Keep the temporary and take its path, the same as [`release`](struct.Temp.html#method.release).
+///
+/// This is for returning a temporary from a function that returns a `PathBuf`, which with
+/// `to_path_buf()` would remove it as the guard goes out of scope, before the caller gets to
+/// use it. The conversion has to be written out, so keeping the temporary is a visible choice.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::Temp;
+/// use std::fs;
+/// use std::path::PathBuf;
+///
+/// fn render() -> PathBuf {
+///     let output = Temp::new_file().unwrap();
+///     fs::write(&output, "<html></html>").unwrap();
+///     // `output.to_path_buf()` here would return a path that no longer exists.
+///     PathBuf::from(output)
+/// }
+///
+/// let path = render();
+/// assert!(path.exists());
+/// # fs::remove_file(path).unwrap();
+/// ```
+impl From<Temp> for PathBuf {
+    fn from(temp: Temp) -> Self {
+        temp.release()
+    }
+}
+
 impl ops::Deref for Temp {
     type Target = PathBuf;
     fn deref(&self) -> &Self::Target {
@@ -173,24 +1177,57 @@ impl ops::Deref for Temp {
     }
 }
 
-impl ops::DerefMut for Temp {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.path
-    }
-}
-
 impl Drop for Temp {
     fn drop(&mut self) {
-        // Drop is blocking (make non-blocking?)
-        if !self.path.exists() {
+        if !self.armed {
+            return;
+        }
+        if self.fs.is_none() && config::keep() {
+            registry::untrack(&self.path);
             return;
         }
 
-        let _result = if self.path.is_dir() {
-            fs::remove_dir_all(&self)
+        if thread::panicking() && self.fs.is_none() {
+            if let Some(ref dir) = self.crash_artifacts {
+                let preserved = self.preserve_crash_artifact(dir);
+                log::warn(format_args!(
+                    "panicked, preserved {} for inspection",
+                    preserved.display()
+                ));
+                registry::untrack(&self.path);
+                return;
+            }
+        }
+
+        if let Err(e) = self.cleanup_within_deadline() {
+            if !self.armed {
+                log::warn(format_args!("{}", e));
+                return;
+            }
+            if self.remove_on_reboot() {
+                registry::untrack(&self.path);
+                return;
+            }
+
+            let strict = self
+                .strict
+                .unwrap_or_else(|| STRICT.load(Ordering::Relaxed));
+
+            if cfg!(debug_assertions) && strict && !thread::panicking() {
+                match self.creation_site() {
+                    Some(site) => panic!(
+                        "failed to remove {} created at {}: {}",
+                        self.describe(),
+                        site,
+                        e
+                    ),
+                    None => panic!("failed to remove {}: {}", self.describe(), e),
+                }
+            }
         } else {
-            fs::remove_file(&self)
-        };
+            registry::untrack(&self.path);
+            tombstone::bury(&self.path, self.creation_site());
+        }
     }
 }
 
@@ -305,6 +1342,237 @@ mod tests {
         }
     }
 
+    #[test]
+    fn file_guard_does_not_remove_swapped_in_dir() {
+        let temp_file = Temp::new_file().unwrap();
+        let path = temp_file.to_path_buf();
+
+        fs::remove_file(&path).unwrap();
+        fs::create_dir(&path).unwrap();
+        drop(temp_file);
+
+        assert!(path.is_dir());
+        fs::remove_dir(path).unwrap();
+    }
+
+    #[test]
+    fn uninitialized_dir() {
+        let temp = Temp::new_path();
+        fs::create_dir(&temp).unwrap();
+        fs::write(temp.join("file"), b"data").unwrap();
+
+        let path = temp.to_path_buf();
+        drop(temp);
+        assert!(!path.exists());
+    }
+
+    struct Undeletable;
+
+    impl TempFs for Undeletable {
+        fn create_file(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn create_dir(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn remove(&self, _path: &Path) -> io::Result<()> {
+            Err(io::Error::new(io::ErrorKind::PermissionDenied, "denied"))
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to remove")]
+    #[cfg(debug_assertions)]
+    fn strict_mode_panics_on_failed_cleanup() {
+        let _temp = Builder::new()
+            .fs(Arc::new(Undeletable))
+            .strict(true)
+            .file()
+            .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to remove")]
+    #[cfg(debug_assertions)]
+    fn delete_on_reboot_does_not_apply_to_custom_backends() {
+        let _temp = Builder::new()
+            .fs(Arc::new(Undeletable))
+            .strict(true)
+            .delete_on_reboot(true)
+            .file()
+            .unwrap();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn creation_site_is_the_caller() {
+        let line = line!() + 1;
+        let temp = Temp::new_file().unwrap();
+        let site = temp.creation_site().unwrap();
+
+        assert_eq!(site.file(), file!());
+        assert_eq!(site.line(), line);
+        assert!(format!("{:?}", temp).contains(&format!("{}:{}", file!(), line)));
+    }
+
+    #[test]
+    fn created_at_is_recorded() {
+        let before = SystemTime::now();
+        let temp = Temp::new_path();
+        assert!(temp.created_at() >= before);
+        assert!(temp.created_at() <= SystemTime::now());
+    }
+
+    #[test]
+    fn close_returns_cleanup_errors() {
+        let temp = Builder::new().fs(Arc::new(Undeletable)).file().unwrap();
+        match temp.close() {
+            Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn failed_cleanup_is_silent_by_default() {
+        let _temp = Builder::new().fs(Arc::new(Undeletable)).file().unwrap();
+    }
+
+    #[test]
+    fn cleanup_hooks_run_in_order_before_removal() {
+        let temp_file = Temp::new_file().unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..2 {
+            let seen = seen.clone();
+            temp_file.on_cleanup(move |path| seen.lock().unwrap().push((i, path.exists())));
+        }
+        temp_file.close().unwrap();
+        assert_eq!(*seen.lock().unwrap(), [(0, true), (1, true)]);
+
+        let temp_file = Temp::new_file().unwrap();
+        let ran = Arc::new(AtomicBool::new(false));
+        let hook_ran = ran.clone();
+        temp_file.on_cleanup(move |_| hook_ran.store(true, Ordering::Relaxed));
+        fs::remove_file(temp_file.release()).unwrap();
+        assert!(!ran.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn panicking_drop_preserves_crash_artifacts() {
+        let artifacts = Temp::new_dir().unwrap();
+        let artifacts_path = artifacts.join("crashes");
+        let path = thread::spawn(move || {
+            let temp_dir = Builder::new()
+                .crash_artifacts(&artifacts_path)
+                .dir()
+                .unwrap();
+            fs::write(temp_dir.join("log"), "evidence").unwrap();
+            let path = temp_dir.to_path_buf();
+            let _guard = temp_dir;
+            std::panic::panic_any(path)
+        })
+        .join()
+        .unwrap_err()
+        .downcast::<PathBuf>()
+        .unwrap();
+
+        assert!(!path.exists());
+        let preserved = artifacts.join("crashes").join(path.file_name().unwrap());
+        assert_eq!(
+            fs::read_to_string(preserved.join("log")).unwrap(),
+            "evidence"
+        );
+
+        let temp_file = Builder::new().crash_artifacts(&artifacts).file().unwrap();
+        let path = temp_file.to_path_buf();
+        drop(temp_file);
+        assert!(!path.exists());
+        assert!(!artifacts.join(path.file_name().unwrap()).exists());
+    }
+
+    #[test]
+    fn disarmed_guard_is_kept_until_rearmed() {
+        let mut temp_file = Temp::new_file().unwrap();
+        let path = temp_file.to_path_buf();
+        temp_file.disarm();
+        temp_file.disarm();
+        assert!(!temp_file.is_armed());
+        temp_file.rearm();
+        drop(temp_file);
+        assert!(!path.exists());
+
+        let mut temp_file = Temp::new_file().unwrap();
+        let path = temp_file.to_path_buf();
+        temp_file.disarm();
+        drop(temp_file);
+        assert!(path.exists());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn label_is_described() {
+        let temp = Builder::new().label("index").path();
+        assert_eq!(temp.label(), Some("index"));
+        assert_eq!(temp.describe(), format!("{} (index)", temp.display()));
+        assert!(format!("{:?}", temp).contains("label: \"index\""));
+        assert_eq!(Temp::new_path().label(), None);
+    }
+
+    #[test]
+    fn expect_empty_keeps_dir_with_entries() {
+        let temp_dir = Builder::new().expect_empty(true).dir().unwrap();
+        let path = temp_dir.to_path_buf();
+        fs::write(temp_dir.join("stray"), "").unwrap();
+        let e = temp_dir.close().unwrap_err();
+        assert!(e.to_string().contains("stray"));
+        assert!(path.join("stray").exists());
+        fs::remove_dir_all(&path).unwrap();
+
+        let temp_dir = Builder::new().expect_empty(true).temp_dir().unwrap();
+        temp_dir.assert_empty();
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn sealed_file_is_synced() {
+        let temp = Builder::new().sync_on_release(true).file().unwrap();
+        fs::write(&temp, b"final").unwrap();
+        temp.seal().unwrap();
+        temp.sync().unwrap();
+
+        let target = Temp::new_path();
+        temp.persist_to(&target).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"final");
+    }
+
+    #[test]
+    fn released_file_is_synced() {
+        let temp = Builder::new().sync_on_release(true).file().unwrap();
+        fs::write(&temp, b"durable").unwrap();
+
+        let path = temp.release();
+        assert_eq!(fs::read(&path).unwrap(), b"durable");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn sync_dir() {
+        let temp = Temp::new_dir().unwrap();
+        temp.sync().unwrap();
+    }
+
+    #[test]
+    fn sealed_file_is_removed() {
+        let temp = Temp::new_file().unwrap();
+        temp.seal().unwrap();
+        assert!(fs::metadata(&temp).unwrap().permissions().readonly());
+
+        let path = temp.to_path_buf();
+        drop(temp);
+        assert!(!path.exists());
+    }
+
     #[test]
     fn uninitialized_file() {
         let temp = Temp::new_path();
@@ -313,6 +1581,51 @@ mod tests {
         assert!(temp.exists());
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn set_mode_and_readonly() {
+        let temp = Temp::new_file().unwrap();
+        temp.set_mode(0o640).unwrap();
+        assert_eq!(temp.symlink_metadata().unwrap().mode() & 0o777, 0o640);
+
+        temp.set_readonly(true).unwrap();
+        assert_eq!(temp.symlink_metadata().unwrap().mode() & 0o777, 0o440);
+        temp.set_readonly(false).unwrap();
+        assert_eq!(temp.symlink_metadata().unwrap().mode() & 0o777, 0o640);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn sealing_round_trip_keeps_mode() {
+        let temp = Temp::new_file().unwrap();
+        temp.seal().unwrap();
+        temp.set_readonly(false).unwrap();
+        assert_eq!(temp.symlink_metadata().unwrap().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn touch_moves_modification_time_forward() {
+        let temp = Temp::new_file().unwrap();
+        let long_ago = UNIX_EPOCH + ::std::time::Duration::from_secs(1_000_000);
+        temp.set_modified(long_ago).unwrap();
+        assert_eq!(
+            temp.symlink_metadata().unwrap().modified().unwrap(),
+            long_ago
+        );
+
+        temp.touch().unwrap();
+        assert!(temp.symlink_metadata().unwrap().modified().unwrap() > long_ago);
+    }
+
+    #[test]
+    fn uninitialized_path_with_extension_in() {
+        let dir = Temp::new_dir().unwrap();
+        let temp = Temp::new_path_with_extension_in(&dir, "db");
+        assert_eq!(temp.parent(), Some(dir.as_path()));
+        assert_eq!(temp.extension().unwrap(), "db");
+        assert!(!temp.exists());
+    }
+
     #[test]
     fn uninitialized_no_panic_on_drop_with_release() {
         let t = Temp::new_path();
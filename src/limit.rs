@@ -0,0 +1,180 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What happens to a creation that would go over a [`CreationLimit`](struct.CreationLimit.html).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OnLimit {
+    /// Wait until it is within the limit again, the default.
+    #[default]
+    Block,
+    /// Fail with `WouldBlock`.
+    Error,
+}
+
+/// Limits on creating temporaries, set with
+/// [`set_creation_limit`](fn.set_creation_limit.html), to protect shared machines from code
+/// creating temporaries in a runaway loop.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CreationLimit {
+    /// The most temporaries created in any one second.
+    pub per_second: Option<u32>,
+    /// The most guards alive at once, counting every temporary file and directory created by
+    /// this crate.
+    pub max_live: Option<usize>,
+    /// What happens to a creation over the limit.
+    pub on_limit: OnLimit,
+}
+
+struct State {
+    limit: Option<CreationLimit>,
+    window_start: Option<Instant>,
+    in_window: u32,
+}
+
+static LIMITED: AtomicBool = AtomicBool::new(false);
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static STATE: Mutex<State> = Mutex::new(State {
+    limit: None,
+    window_start: None,
+    in_window: 0,
+});
+static FREED: Condvar = Condvar::new();
+
+const SECOND: Duration = Duration::from_secs(1);
+
+fn state() -> MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Limit how fast temporaries are created and how many are alive at once, or lift the limit
+/// with `None`. Every file and directory created is checked. Paths from
+/// [`Temp::new_path`](struct.Temp.html#method.new_path) don't count, as nothing is created for
+/// them until the caller does.
+///
+/// The number alive is checked before each creation, so concurrent creations can go over
+/// `max_live` by a few.
+///
+/// # Panics
+///
+/// Panics if `per_second` or `max_live` is zero.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::{CreationLimit, OnLimit, Temp};
+/// use std::io;
+///
+/// mktemp::set_creation_limit(Some(CreationLimit {
+///     max_live: Some(1),
+///     on_limit: OnLimit::Error,
+///     ..CreationLimit::default()
+/// }));
+///
+/// let first = Temp::new_file().unwrap();
+/// let error = Temp::new_file().unwrap_err();
+/// assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+///
+/// drop(first);
+/// let second = Temp::new_file().unwrap();
+/// # mktemp::set_creation_limit(None);
+/// ```
+pub fn set_creation_limit(limit: Option<CreationLimit>) {
+    if let Some(limit) = limit {
+        assert!(limit.per_second != Some(0), "per_second must not be zero");
+        assert!(limit.max_live != Some(0), "max_live must not be zero");
+    }
+    let mut state = state();
+    state.limit = limit;
+    state.window_start = None;
+    LIMITED.store(limit.is_some(), Ordering::Relaxed);
+    // Blocked creations may be within the new limit.
+    FREED.notify_all();
+}
+
+/// Wait until a temporary can be created within the limit, or fail if it is set to.
+pub(crate) fn admit() -> io::Result<()> {
+    if !LIMITED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+
+    let mut state = state();
+    loop {
+        let limit = match state.limit {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+
+        if let Some(max_live) = limit.max_live {
+            if LIVE.load(Ordering::SeqCst) >= max_live {
+                if limit.on_limit == OnLimit::Error {
+                    return Err(over_limit(format_args!(
+                        "{} temporaries are alive",
+                        max_live
+                    )));
+                }
+                state = FREED.wait(state).unwrap_or_else(|e| e.into_inner());
+                continue;
+            }
+        }
+
+        if let Some(per_second) = limit.per_second {
+            let now = Instant::now();
+            let start = match state.window_start {
+                Some(start) if now.duration_since(start) < SECOND => start,
+                _ => {
+                    state.window_start = Some(now);
+                    state.in_window = 0;
+                    now
+                }
+            };
+            if state.in_window >= per_second {
+                if limit.on_limit == OnLimit::Error {
+                    return Err(over_limit(format_args!(
+                        "{} temporaries were created in the last second",
+                        per_second
+                    )));
+                }
+                drop(state);
+                thread::sleep(SECOND - now.duration_since(start));
+                state = self::state();
+                continue;
+            }
+            state.in_window += 1;
+        }
+        return Ok(());
+    }
+}
+
+fn over_limit(reason: ::std::fmt::Arguments) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::WouldBlock,
+        format!("over the creation limit: {}", reason),
+    )
+}
+
+/// Counts a guard as alive for the `max_live` limit.
+#[derive(Debug)]
+pub(crate) struct Live(());
+
+impl Live {
+    pub(crate) fn new() -> Live {
+        LIVE.fetch_add(1, Ordering::SeqCst);
+        Live(())
+    }
+}
+
+impl Drop for Live {
+    fn drop(&mut self) {
+        LIVE.fetch_sub(1, Ordering::SeqCst);
+        if LIMITED.load(Ordering::Relaxed) {
+            let _state = state();
+            FREED.notify_all();
+        }
+    }
+}
@@ -0,0 +1,32 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fmt;
+use std::sync::Mutex;
+
+type Hook = Box<dyn Fn(&str) + Send + Sync>;
+
+static HOOK: Mutex<Option<Hook>> = Mutex::new(None);
+
+/// Receive the warnings the crate emits when it works around a problem, e.g. a full disk, so
+/// they can be forwarded to the application's logger. Warnings are dropped until a hook is
+/// set.
+///
+/// # Examples
+///
+/// ```
+/// mktemp::set_log_hook(|message| eprintln!("mktemp: {}", message));
+/// ```
+pub fn set_log_hook<F>(hook: F)
+where
+    F: Fn(&str) + Send + Sync + 'static,
+{
+    *HOOK.lock().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(hook));
+}
+
+pub(crate) fn warn(message: fmt::Arguments) {
+    let hook = HOOK.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(ref hook) = *hook {
+        hook(&message.to_string());
+    }
+}
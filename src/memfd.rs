@@ -0,0 +1,173 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! Anonymous in-memory files on Linux, which can be sealed so consumers can rely on their
+//! contents never changing.
+//!
+//! # Examples
+//!
+//! ```
+//! use mktemp::memfd::{MemFile, Seals};
+//! use std::io::Write;
+//!
+//! let mut file = MemFile::new("plugin-input").unwrap();
+//! file.write_all(b"immutable").unwrap();
+//! file.add_seals(Seals::WRITE | Seals::GROW | Seals::SHRINK | Seals::SEAL).unwrap();
+//!
+//! assert!(file.write_all(b"more").is_err());
+//! ```
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+
+/// A set of seals, see `fcntl(2)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Seals(i32);
+
+impl Seals {
+    /// No seals.
+    pub const NONE: Seals = Seals(0);
+    /// The contents can't be modified.
+    pub const WRITE: Seals = Seals(::libc::F_SEAL_WRITE);
+    /// The file can't grow.
+    pub const GROW: Seals = Seals(::libc::F_SEAL_GROW);
+    /// The file can't shrink.
+    pub const SHRINK: Seals = Seals(::libc::F_SEAL_SHRINK);
+    /// No further seals can be added.
+    pub const SEAL: Seals = Seals(::libc::F_SEAL_SEAL);
+
+    /// Whether every seal in `other` is also in `self`.
+    pub fn contains(self, other: Seals) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl ops::BitOr for Seals {
+    type Output = Seals;
+    fn bitor(self, other: Seals) -> Seals {
+        Seals(self.0 | other.0)
+    }
+}
+
+/// An anonymous file which only lives in memory, created with `memfd_create(2)`.
+#[derive(Debug)]
+pub struct MemFile {
+    file: File,
+}
+
+impl MemFile {
+    /// Create an empty in-memory file which can be sealed. `name` is only used for debugging,
+    /// e.g. in `/proc/self/fd`.
+    pub fn new(name: &str) -> io::Result<Self> {
+        let name =
+            CString::new(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let flags = ::libc::MFD_CLOEXEC | ::libc::MFD_ALLOW_SEALING;
+
+        let fd = unsafe { ::libc::syscall(::libc::SYS_memfd_create, name.as_ptr(), flags) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(MemFile {
+            file: unsafe { File::from_raw_fd(fd as RawFd) },
+        })
+    }
+
+    /// Add `seals`. Seals can't be removed once added.
+    pub fn add_seals(&self, seals: Seals) -> io::Result<()> {
+        let result = unsafe { ::libc::fcntl(self.file.as_raw_fd(), ::libc::F_ADD_SEALS, seals.0) };
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The seals currently in place.
+    pub fn seals(&self) -> io::Result<Seals> {
+        let seals = unsafe { ::libc::fcntl(self.file.as_raw_fd(), ::libc::F_GET_SEALS) };
+        if seals < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(Seals(seals))
+        }
+    }
+
+    /// Borrow the underlying file, e.g. to map or share it.
+    pub fn as_file(&self) -> &File {
+        &self.file
+    }
+
+    /// Take the underlying file.
+    pub fn into_file(self) -> File {
+        self.file
+    }
+}
+
+impl Read for MemFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for MemFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for MemFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl AsRawFd for MemFile {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for MemFile {
+    fn into_raw_fd(self) -> RawFd {
+        self.file.into_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_file_is_immutable() {
+        let mut file = MemFile::new("test").unwrap();
+        file.write_all(b"data").unwrap();
+        assert_eq!(file.seals().unwrap(), Seals::NONE);
+
+        file.add_seals(Seals::WRITE | Seals::GROW).unwrap();
+        let seals = file.seals().unwrap();
+        assert!(seals.contains(Seals::WRITE | Seals::GROW));
+        assert!(!seals.contains(Seals::SHRINK));
+
+        assert!(file.write_all(b"more").is_err());
+        assert!(file.as_file().set_len(100).is_err());
+
+        let mut contents = String::new();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "data");
+    }
+
+    #[test]
+    fn seal_seal_prevents_more_seals() {
+        let file = MemFile::new("test").unwrap();
+        file.add_seals(Seals::SEAL).unwrap();
+        assert!(file.add_seals(Seals::WRITE).is_err());
+    }
+}
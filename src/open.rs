@@ -0,0 +1,159 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fs::{File, OpenOptions};
+use std::io;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::os::unix::fs::OpenOptionsExt;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "macos",
+    target_os = "ios"
+))]
+use std::os::unix::io::AsRawFd;
+#[cfg(windows)]
+use std::os::windows::fs::OpenOptionsExt;
+
+use Temp;
+
+/// An access pattern hint for the page cache, see `posix_fadvise(2)`.
+///
+/// Hints are only applied on Linux, Android and FreeBSD and are ignored elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Advice {
+    /// No particular access pattern.
+    Normal,
+    /// The data is accessed from start to end.
+    Sequential,
+    /// The data is accessed in random order.
+    Random,
+    /// The data is accessed only once.
+    NoReuse,
+    /// The data will be accessed soon.
+    WillNeed,
+    /// The data won't be accessed again, so its clean pages can be dropped from the cache.
+    DontNeed,
+}
+
+/// Give the kernel an access pattern hint for the whole of `file`.
+///
+/// `DontNeed` only drops pages that have been written back, so after a large write call
+/// [`File::sync_data`](https://doc.rust-lang.org/std/fs/struct.File.html#method.sync_data)
+/// first to keep the write from evicting the rest of the page cache.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::{advise, Advice, Temp};
+/// use std::io::Write;
+///
+/// let temp_file = Temp::new_file().unwrap();
+/// let mut file = temp_file.open_with_advice(Advice::Sequential).unwrap();
+/// file.write_all(&[0; 4096]).unwrap();
+/// file.sync_data().unwrap();
+/// advise(&file, Advice::DontNeed).unwrap();
+/// ```
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+pub fn advise(file: &File, advice: Advice) -> io::Result<()> {
+    let advice = match advice {
+        Advice::Normal => ::libc::POSIX_FADV_NORMAL,
+        Advice::Sequential => ::libc::POSIX_FADV_SEQUENTIAL,
+        Advice::Random => ::libc::POSIX_FADV_RANDOM,
+        Advice::NoReuse => ::libc::POSIX_FADV_NOREUSE,
+        Advice::WillNeed => ::libc::POSIX_FADV_WILLNEED,
+        Advice::DontNeed => ::libc::POSIX_FADV_DONTNEED,
+    };
+
+    // posix_fadvise returns the error instead of setting errno.
+    match unsafe { ::libc::posix_fadvise(file.as_raw_fd(), 0, 0, advice) } {
+        0 => Ok(()),
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+/// Give the kernel an access pattern hint for the whole of `file`.
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+pub fn advise(_file: &File, _advice: Advice) -> io::Result<()> {
+    Ok(())
+}
+
+impl Temp {
+    /// Open the temporary file for reading and writing with an access pattern hint, e.g.
+    /// `Advice::NoReuse` for large one-pass writes that shouldn't evict the page cache of the
+    /// host. See [`advise`](fn.advise.html).
+    pub fn open_with_advice(&self, advice: Advice) -> io::Result<File> {
+        let file = OpenOptions::new().read(true).write(true).open(self)?;
+        advise(&file, advice)?;
+        Ok(file)
+    }
+
+    /// Open the temporary file for reading and writing, bypassing the page cache.
+    ///
+    /// This uses `O_DIRECT` on Linux, `F_NOCACHE` on macOS and `FILE_FLAG_NO_BUFFERING` on
+    /// Windows, and opens the file normally elsewhere. With `O_DIRECT` and
+    /// `FILE_FLAG_NO_BUFFERING` the buffers, offsets and lengths of every read and write must
+    /// be multiples of the device's logical block size, 4096 bytes covers most devices, or the
+    /// call fails with `EINVAL`. Some file systems, e.g. tmpfs, don't support direct I/O and
+    /// fail to open with `EINVAL`.
+    pub fn open_direct(&self) -> io::Result<File> {
+        let mut options = OpenOptions::new();
+        options.read(true).write(true);
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        options.custom_flags(::libc::O_DIRECT);
+        #[cfg(windows)]
+        options.custom_flags(0x2000_0000); // FILE_FLAG_NO_BUFFERING
+
+        let file = options.open(self)?;
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        {
+            if unsafe { ::libc::fcntl(file.as_raw_fd(), ::libc::F_NOCACHE, 1) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    #[test]
+    fn advice_does_not_change_contents() {
+        let temp = Temp::new_file().unwrap();
+        let mut file = temp.open_with_advice(Advice::Sequential).unwrap();
+        file.write_all(b"data").unwrap();
+        file.sync_data().unwrap();
+        advise(&file, Advice::DontNeed).unwrap();
+
+        let mut contents = String::new();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "data");
+    }
+
+    #[test]
+    fn direct_io_with_aligned_buffers() {
+        #[repr(align(4096))]
+        struct Block([u8; 4096]);
+
+        let temp = Temp::new_file().unwrap();
+        let mut file = match temp.open_direct() {
+            Ok(file) => file,
+            // The file system doesn't support direct I/O.
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidInput => return,
+            Err(e) => panic!("{}", e),
+        };
+
+        let block = Block([7; 4096]);
+        file.write_all(&block.0).unwrap();
+
+        let mut read = Block([0; 4096]);
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_exact(&mut read.0).unwrap();
+        assert!(read.0.iter().all(|&b| b == 7));
+    }
+}
@@ -0,0 +1,95 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! Errors that name the path they happened on, like `fs-err`, enabled with the `path_errors`
+//! feature.
+//!
+//! An annotated error keeps the kind of the original, which is available as its `source`.
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+struct PathError {
+    context: String,
+    source: io::Error,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed to {}: {}", self.context, self.source)
+    }
+}
+
+impl Error for PathError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+#[cfg(feature = "path_errors")]
+fn wrap(source: io::Error, context: String) -> io::Error {
+    io::Error::new(source.kind(), PathError { context, source })
+}
+
+/// Name `path` in the error of `result`, e.g. "failed to remove file `/tmp/x`: ...".
+#[cfg_attr(not(feature = "path_errors"), allow(unused_variables))]
+pub(crate) fn annotate<T>(result: io::Result<T>, operation: &str, path: &Path) -> io::Result<T> {
+    #[cfg(feature = "path_errors")]
+    {
+        result.map_err(|e| wrap(e, format!("{} `{}`", operation, path.display())))
+    }
+    #[cfg(not(feature = "path_errors"))]
+    {
+        result
+    }
+}
+
+/// Name both paths in the error of a rename or copy.
+#[cfg_attr(not(feature = "path_errors"), allow(unused_variables))]
+pub(crate) fn annotate2<T>(
+    result: io::Result<T>,
+    operation: &str,
+    from: &Path,
+    to: &Path,
+) -> io::Result<T> {
+    #[cfg(feature = "path_errors")]
+    {
+        result.map_err(|e| {
+            let context = format!("{} `{}` to `{}`", operation, from.display(), to.display());
+            wrap(e, context)
+        })
+    }
+    #[cfg(not(feature = "path_errors"))]
+    {
+        result
+    }
+}
+
+/// The OS error code of `e`, looking through an annotation.
+pub(crate) fn raw_os_error(e: &io::Error) -> Option<i32> {
+    e.raw_os_error().or_else(|| {
+        e.get_ref()
+            .and_then(|inner| inner.downcast_ref::<PathError>())
+            .and_then(|inner| inner.source.raw_os_error())
+    })
+}
+
+#[cfg(all(test, feature = "path_errors"))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use Temp;
+
+    #[test]
+    fn errors_name_the_path_and_keep_the_code() {
+        let dir = Temp::new_dir().unwrap();
+        let missing = dir.join("missing");
+
+        let e = annotate(fs::remove_file(&missing), "remove file", &missing).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        assert!(e.to_string().contains(&*missing.to_string_lossy()));
+        assert!(raw_os_error(&e).is_some());
+    }
+}
@@ -0,0 +1,279 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::ops;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+#[cfg(windows)]
+use std::os::windows::fs::OpenOptionsExt;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+
+#[cfg(not(unix))]
+use sys;
+use {Builder, Temp};
+
+type Idle = Mutex<Vec<Temp>>;
+
+/// A pool of temporary files which are truncated and reused instead of being removed and
+/// created again, cutting down on file system metadata churn.
+///
+/// Files returned to a full pool are removed, as are all idle files when the pool is dropped.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::TempPool;
+/// use std::fs;
+///
+/// let pool = TempPool::new(4);
+/// let first = {
+///     let file = pool.get().unwrap();
+///     fs::write(&*file, b"scratch").unwrap();
+///     file.to_path_buf()
+/// };
+///
+/// let file = pool.get().unwrap();
+/// assert_eq!(file.to_path_buf(), first);
+/// assert_eq!(fs::metadata(&*file).unwrap().len(), 0);
+/// ```
+pub struct TempPool {
+    idle: Arc<Idle>,
+    builder: Builder,
+    capacity: usize,
+}
+
+impl TempPool {
+    /// Create a pool keeping up to `capacity` idle files.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_builder(Builder::new(), capacity)
+    }
+
+    /// Create a pool keeping up to `capacity` idle files, creating new ones using `builder`.
+    pub fn with_builder(builder: Builder, capacity: usize) -> Self {
+        TempPool {
+            idle: Arc::new(Mutex::new(Vec::with_capacity(capacity))),
+            builder,
+            capacity,
+        }
+    }
+
+    /// Take an empty file from the pool, creating one if none are idle.
+    pub fn get(&self) -> io::Result<PooledFile> {
+        let temp = match lock(&self.idle).pop() {
+            Some(mut temp) => {
+                // Only the pool's own settings carry over to the next borrower.
+                temp.label = self.builder.label_arc();
+                temp
+            }
+            None => self.builder.file()?,
+        };
+
+        Ok(PooledFile {
+            temp: Some(temp),
+            pool: Arc::downgrade(&self.idle),
+            capacity: self.capacity,
+            mode: self.builder.file_mode(),
+        })
+    }
+
+    /// The number of idle files waiting to be reused.
+    pub fn idle(&self) -> usize {
+        lock(&self.idle).len()
+    }
+}
+
+impl fmt::Debug for TempPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TempPool")
+            .field("idle", &*lock(&self.idle))
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+/// A file on loan from a [`TempPool`](struct.TempPool.html), returned to it when dropped.
+#[derive(Debug)]
+pub struct PooledFile {
+    temp: Option<Temp>,
+    pool: Weak<Idle>,
+    capacity: usize,
+    mode: u32,
+}
+
+impl ops::Deref for PooledFile {
+    type Target = Temp;
+    fn deref(&self) -> &Self::Target {
+        self.temp
+            .as_ref()
+            .expect("pooled file is only taken on drop")
+    }
+}
+
+impl AsRef<Path> for PooledFile {
+    fn as_ref(&self) -> &Path {
+        (**self).as_ref()
+    }
+}
+
+impl Drop for PooledFile {
+    fn drop(&mut self) {
+        let temp = match self.temp.take() {
+            Some(temp) => temp,
+            None => return,
+        };
+        let pool = match self.pool.upgrade() {
+            Some(pool) => pool,
+            None => return,
+        };
+
+        temp.take_cleanup_hooks();
+        // A file that can't be reset, e.g. because it was removed or replaced, or frozen, isn't
+        // worth keeping.
+        let reset = if temp.frozen.load(Ordering::Relaxed) {
+            Err(io::ErrorKind::PermissionDenied.into())
+        } else {
+            reset(&temp, self.mode)
+        };
+
+        let mut idle = lock(&pool);
+        if reset.is_ok() && idle.len() < self.capacity {
+            idle.push(temp);
+        }
+    }
+}
+
+/// Truncate the file at `path` and give it `mode` again, without opening anything but a regular
+/// file the borrower may have put in its place.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn reset(path: &Path, mode: u32) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_file() {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+
+    // Undo `set_mode` or `seal` by the borrower first, so the file can be opened for writing.
+    // A symlink swapped in after the check above only gets the pool's mode, and fails the
+    // `O_NOFOLLOW` open below.
+    let mut permissions = metadata.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(mode);
+    }
+    #[cfg(not(unix))]
+    sys::set_readonly(&mut permissions, false);
+    fs::set_permissions(path, permissions)?;
+
+    let mut options = OpenOptions::new();
+    options.write(true);
+    #[cfg(unix)]
+    options.custom_flags(::libc::O_NOFOLLOW | ::libc::O_NONBLOCK);
+    #[cfg(windows)]
+    options.custom_flags(0x0020_0000); // FILE_FLAG_OPEN_REPARSE_POINT
+    let file = options.open(path)?;
+    if !file.metadata()?.is_file() {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    file.set_len(0)
+}
+
+fn lock(idle: &Idle) -> MutexGuard<'_, Vec<Temp>> {
+    idle.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn files_are_reused_up_to_capacity() {
+        let pool = TempPool::new(1);
+        let first = pool.get().unwrap();
+        let second = pool.get().unwrap();
+        let second_path = second.to_path_buf();
+
+        drop(first);
+        drop(second);
+        assert_eq!(pool.idle(), 1);
+        assert!(!second_path.exists());
+    }
+
+    #[test]
+    fn idle_files_are_removed_with_pool() {
+        let pool = TempPool::new(2);
+        let path = pool.get().unwrap().to_path_buf();
+        assert!(path.exists());
+
+        drop(pool);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn file_outliving_pool_is_removed() {
+        let pool = TempPool::new(2);
+        let file = pool.get().unwrap();
+        let path = file.to_path_buf();
+
+        drop(pool);
+        drop(file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn removed_files_are_not_reused() {
+        let pool = TempPool::new(2);
+        let file = pool.get().unwrap();
+        fs::remove_file(&*file).unwrap();
+
+        drop(file);
+        assert_eq!(pool.idle(), 0);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn replaced_files_are_not_reused() {
+        let target = Temp::new_file().unwrap();
+        fs::write(&target, b"keep").unwrap();
+
+        let pool = TempPool::new(2);
+        let file = pool.get().unwrap();
+        fs::remove_file(&*file).unwrap();
+        ::std::os::unix::fs::symlink(&target, &*file).unwrap();
+        file.on_cleanup(|_| panic!("hook of the previous borrower"));
+
+        drop(file);
+        assert_eq!(pool.idle(), 0);
+        assert_eq!(fs::read(&target).unwrap(), b"keep");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn modes_are_not_passed_on() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let pool = TempPool::new(1);
+        let file = pool.get().unwrap();
+        file.set_mode(0o644).unwrap();
+        file.seal().unwrap();
+        drop(file);
+
+        let file = pool.get().unwrap();
+        let mode = fs::metadata(&*file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn hooks_are_not_passed_on() {
+        let pool = TempPool::new(1);
+        let file = pool.get().unwrap();
+        file.on_cleanup(|_| panic!("hook of the previous borrower"));
+        drop(file);
+        assert_eq!(pool.idle(), 1);
+    }
+}
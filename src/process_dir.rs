@@ -0,0 +1,65 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::io;
+use std::sync::Mutex;
+
+use {config, log, registry, sys, Builder, Temp};
+
+static PROCESS_DIR: Mutex<Option<&'static Temp>> = Mutex::new(None);
+
+/// A temporary directory that lives as long as the process, created on first use, for
+/// libraries that need somewhere to put things without threading a guard through every API.
+///
+/// Every call returns the same directory, from any thread. It is removed when the process
+/// exits normally, by returning from `main` or calling
+/// [`process::exit`](https://doc.rust-lang.org/std/process/fn.exit.html), through the C
+/// runtime's `atexit`; not when it is killed or aborts, including on panic with
+/// `panic = "abort"`. If creating the directory fails, the next call tries again.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+///
+/// let dir = mktemp::process_dir().unwrap();
+/// fs::write(dir.join("index"), "cached").unwrap();
+/// assert_eq!(mktemp::process_dir().unwrap().path(), dir.path());
+/// ```
+pub fn process_dir() -> io::Result<&'static Temp> {
+    let mut process_dir = PROCESS_DIR.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(dir) = *process_dir {
+        return Ok(dir);
+    }
+
+    let dir = Builder::new().prefix("process-").dir()?;
+    if let Err(e) = sys::at_exit(remove_process_dir) {
+        log::warn(format_args!(
+            "{} will not be removed at exit: {}",
+            dir.path().display(),
+            e
+        ));
+    }
+    let dir: &'static Temp = Box::leak(Box::new(dir));
+    *process_dir = Some(dir);
+    Ok(dir)
+}
+
+extern "C" fn remove_process_dir() {
+    let process_dir = PROCESS_DIR.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = match *process_dir {
+        Some(dir) => dir,
+        None => return,
+    };
+    if dir.fs.is_none() && config::keep() {
+        return;
+    }
+    match dir.cleanup() {
+        Ok(()) => registry::untrack(&dir.path),
+        Err(e) => log::warn(format_args!(
+            "failed to remove {}: {}",
+            dir.path.display(),
+            e
+        )),
+    }
+}
@@ -0,0 +1,111 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! A process-wide record of the temporaries that haven't been released or removed yet, kept
+//! while a leak check or usage tracking needs it.
+//!
+//! The record is split into shards by path, so threads creating temporaries concurrently rarely
+//! contend for the same lock, and nothing is locked at all while no one needs the record.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::panic::Location;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub(crate) type Site = Option<&'static Location<'static>>;
+
+/// What is known about a recorded temporary.
+#[derive(Debug, Clone)]
+pub(crate) struct Record {
+    pub site: Site,
+    pub label: Option<Arc<str>>,
+}
+
+type Shard = Mutex<Option<HashMap<PathBuf, Record>>>;
+
+const SHARDS: usize = 32;
+
+static USERS: AtomicUsize = AtomicUsize::new(0);
+/// Serializes enabling and disabling, so the last user clears every shard.
+static USERS_LOCK: Mutex<()> = Mutex::new(());
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY: Shard = Mutex::new(None);
+static TRACKED: [Shard; SHARDS] = [EMPTY; SHARDS];
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn shard(path: &Path) -> MutexGuard<'static, Option<HashMap<PathBuf, Record>>> {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    lock(&TRACKED[hasher.finish() as usize % SHARDS])
+}
+
+fn enabled() -> bool {
+    USERS.load(Ordering::Relaxed) > 0
+}
+
+/// Start recording temporaries.
+pub(crate) fn enable() {
+    let _users = lock(&USERS_LOCK);
+    USERS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Stop recording temporaries, and forget them once nothing needs the record.
+pub(crate) fn disable() {
+    let _users = lock(&USERS_LOCK);
+    if USERS.fetch_sub(1, Ordering::Relaxed) == 1 {
+        for shard in &TRACKED {
+            *lock(shard) = None;
+        }
+    }
+}
+
+pub(crate) fn track(path: &Path, site: Site, label: Option<Arc<str>>) {
+    if enabled() {
+        shard(path)
+            .get_or_insert_with(HashMap::new)
+            .insert(path.to_path_buf(), Record { site, label });
+    }
+}
+
+pub(crate) fn untrack(path: &Path) {
+    if enabled() {
+        if let Some(ref mut tracked) = *shard(path) {
+            tracked.remove(path);
+        }
+    }
+}
+
+pub(crate) fn moved(from: &Path, to: &Path) {
+    if enabled() {
+        // Take the entry out before locking the destination shard, which may be the same one.
+        let record = match *shard(from) {
+            Some(ref mut tracked) => tracked.remove(from),
+            None => None,
+        };
+        if let Some(record) = record {
+            shard(to)
+                .get_or_insert_with(HashMap::new)
+                .insert(to.to_path_buf(), record);
+        }
+    }
+}
+
+/// The recorded temporaries.
+pub(crate) fn snapshot() -> Vec<(PathBuf, Record)> {
+    let mut snapshot = Vec::new();
+    for shard in &TRACKED {
+        if let Some(ref tracked) = *lock(shard) {
+            snapshot.extend(
+                tracked
+                    .iter()
+                    .map(|(path, record)| (path.clone(), record.clone())),
+            );
+        }
+    }
+    snapshot
+}
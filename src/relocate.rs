@@ -0,0 +1,573 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use atomic::{parent_dir, sync_dir};
+use backend::require_disk;
+use limit;
+#[cfg(any(unix, windows))]
+use path_error::raw_os_error;
+use sys;
+use {Builder, Kind, Temp};
+
+#[cfg(unix)]
+fn is_cross_device(e: &io::Error) -> bool {
+    raw_os_error(e) == Some(::libc::EXDEV)
+}
+
+#[cfg(windows)]
+fn is_cross_device(e: &io::Error) -> bool {
+    // ERROR_NOT_SAME_DEVICE
+    raw_os_error(e) == Some(17)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_cross_device(_e: &io::Error) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn device_of(path: &Path) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    fs::metadata(path).map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device_of(path: &Path) -> io::Result<PathBuf> {
+    // Compare the volume prefix, e.g. `\\?\C:`, which is all that's available on stable.
+    let path = fs::canonicalize(path)?;
+    Ok(path.components().take(1).collect())
+}
+
+/// Copy a file or directory tree, without following symlinks.
+pub(crate) fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    let file_type = fs::symlink_metadata(from)?.file_type();
+
+    if file_type.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        // Only once the children are in, as the directory may be read-only.
+        fs::set_permissions(to, fs::metadata(from)?.permissions())
+    } else if file_type.is_symlink() {
+        copy_symlink(from, to)
+    } else {
+        sys::copy_file(from, to)
+    }
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    ::std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    fs::copy(from, to).map(|_| ())
+}
+
+impl Temp {
+    /// Rename the temporary within its directory, the guard keeps responsibility for removing it
+    /// at its new location.
+    ///
+    /// `new_name` must be a plain file name. As with `std::fs::rename`, an existing file with
+    /// that name may be replaced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    ///
+    /// let mut temp_file = Temp::new_file().unwrap();
+    /// temp_file.rename_within("report.csv").unwrap();
+    /// assert!(temp_file.ends_with("report.csv"));
+    /// ```
+    pub fn rename_within<S: AsRef<OsStr>>(&mut self, new_name: S) -> io::Result<()> {
+        let new_name = Path::new(new_name.as_ref());
+
+        let mut components = new_name.components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => (),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is not a plain file name", new_name.display()),
+                ))
+            }
+        }
+
+        let to = self.path.with_file_name(new_name);
+        self.fs().rename(&self.path, &to)?;
+        self.moved_to(to);
+        Ok(())
+    }
+
+    /// Move the temporary into another directory, keeping its name. The guard keeps
+    /// responsibility for removing it at its new location.
+    ///
+    /// When `dir` is on a different file system the temporary is copied and the original
+    /// removed, in which case an error removing the original is returned after the guard has
+    /// been moved to the copy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    ///
+    /// let staging = Temp::new_dir().unwrap();
+    /// let mut artifact = Temp::new_file().unwrap();
+    /// artifact.move_into(&staging).unwrap();
+    /// assert!(artifact.starts_with(&staging));
+    /// ```
+    pub fn move_into<P: AsRef<Path>>(&mut self, dir: P) -> io::Result<()> {
+        let to = dir.as_ref().join(self.name());
+
+        match self.fs().rename(&self.path, &to) {
+            Ok(()) => {
+                self.moved_to(to);
+                Ok(())
+            }
+            Err(ref e) if is_cross_device(e) => {
+                if fs::symlink_metadata(&to).is_ok() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} already exists", to.display()),
+                    ));
+                }
+                if let Err(e) = copy_recursive(&self.path, &to) {
+                    let _result = fs::remove_dir_all(&to).or_else(|_| fs::remove_file(&to));
+                    return Err(e);
+                }
+
+                let from = self.moved_to(to);
+                if fs::symlink_metadata(&from)?.is_dir() {
+                    fs::remove_dir_all(&from)
+                } else {
+                    fs::remove_file(&from)
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Create a temporary file next to `target`, so that a later
+    /// [`persist_to`](#method.persist_to) of `target` is an atomic rename.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    ///
+    /// let output_dir = Temp::new_dir().unwrap();
+    /// let target = output_dir.join("report.csv");
+    ///
+    /// let temp_file = Temp::new_file_near(&target).unwrap();
+    /// fs::write(&temp_file, "id,name\n").unwrap();
+    /// assert!(temp_file.same_device(&target).unwrap());
+    ///
+    /// temp_file.persist_to(&target).unwrap();
+    /// assert_eq!(fs::read_to_string(&target).unwrap(), "id,name\n");
+    /// ```
+    #[track_caller]
+    pub fn new_file_near<P: AsRef<Path>>(target: P) -> io::Result<Temp> {
+        let target = target.as_ref();
+        let prefix = match target.file_name() {
+            Some(name) => format!(".{}.", name.to_string_lossy()),
+            None => ".".to_string(),
+        };
+
+        Builder::new()
+            .in_dir(parent_dir(target)?)
+            .prefix(&prefix)
+            .file()
+    }
+
+    /// Whether the temporary is on the same file system as `target`, i.e. whether it can be
+    /// renamed to `target` without copying. `target` doesn't have to exist, its parent
+    /// directory does.
+    pub fn same_device<P: AsRef<Path>>(&self, target: P) -> io::Result<bool> {
+        let target = target.as_ref();
+        let target_device = match device_of(target) {
+            Ok(device) => device,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => device_of(parent_dir(target)?)?,
+            Err(e) => return Err(e),
+        };
+
+        Ok(device_of(&self.path)? == target_device)
+    }
+
+    /// Keep the temporary by renaming it to `target`, replacing an existing file, and return
+    /// the new path.
+    ///
+    /// If `target` is on another file system the temporary is first copied next to `target`,
+    /// so `target` is still replaced atomically. The temporary is synced first if
+    /// [`Builder::sync_on_release`](struct.Builder.html#method.sync_on_release) is set. On
+    /// failure the temporary is removed like it is when dropped.
+    pub fn persist_to<P: AsRef<Path>>(self, target: P) -> io::Result<PathBuf> {
+        let target = target.as_ref();
+        if self.sync_on_release {
+            self.sync()?;
+        }
+
+        match self.fs().rename(&self.path, target) {
+            Ok(()) => {}
+            Err(ref e) if is_cross_device(e) => {
+                let staged = Builder::new()
+                    .in_dir(parent_dir(target)?)
+                    .prefix(".persist-")
+                    .try_path()?;
+                copy_recursive(&self.path, &staged)?;
+                fs::rename(&staged, target)?;
+                staged.release();
+                let _result = self.fs().remove(&self.path);
+            }
+            Err(e) => return Err(e),
+        }
+
+        if self.sync_on_release {
+            sync_dir(parent_dir(target)?)?;
+        }
+        self.defuse();
+        Ok(target.to_path_buf())
+    }
+
+    /// Create a temporary file holding a copy of `src`.
+    ///
+    /// On file systems with copy-on-write support (Btrfs, XFS, APFS) the copy shares the
+    /// original's data, so even large files are copied instantly and take no extra space.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    ///
+    /// let original = Temp::new_file().unwrap();
+    /// fs::write(&original, b"fixture").unwrap();
+    ///
+    /// let copy = Temp::copy_from(&original).unwrap();
+    /// assert_eq!(fs::read(&copy).unwrap(), b"fixture");
+    /// ```
+    #[track_caller]
+    pub fn copy_from<P: AsRef<Path>>(src: P) -> io::Result<Temp> {
+        let builder = Builder::new();
+        limit::admit()?;
+        let path = builder.create_path()?;
+        sys::copy_file(src.as_ref(), &path)?;
+        Ok(builder.wrap(path, Kind::File))
+    }
+
+    /// Create another guard for the same file, as a hard link next to it, so several components
+    /// can each own a path to the same large content. Each guard removes its own link; the data
+    /// is reclaimed when the last one is dropped. The alias has the same label.
+    ///
+    /// Fails with `InvalidInput` unless the temporary was created as a file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    ///
+    /// let original = Temp::new_file().unwrap();
+    /// fs::write(&original, b"large blob").unwrap();
+    ///
+    /// let alias = original.alias().unwrap();
+    /// drop(original);
+    /// assert_eq!(fs::read(&alias).unwrap(), b"large blob");
+    /// ```
+    #[track_caller]
+    pub fn alias(&self) -> io::Result<Temp> {
+        require_disk(self.fs(), "alias")?;
+        if self.kind != Kind::File {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a file, it can't be aliased", self.describe()),
+            ));
+        }
+
+        let mut builder = Builder::new();
+        builder.in_dir(parent_dir(&self.path)?);
+        if let Some(label) = self.label() {
+            builder.label(label);
+        }
+        limit::admit()?;
+        let path = builder.create_path()?;
+        fs::hard_link(&self.path, &path)?;
+        Ok(builder.wrap(path, Kind::File))
+    }
+
+    /// Create a temporary directory holding a copy of the directory tree at `src`, cloning file
+    /// data like [`copy_from`](#method.copy_from).
+    #[track_caller]
+    pub fn dir_from<P: AsRef<Path>>(src: P) -> io::Result<Temp> {
+        let temp = Temp::new_dir()?;
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &temp.join(entry.file_name()))?;
+        }
+        Ok(temp)
+    }
+
+    /// Exchange the temporary with the file at `path`, so `path` has the temporary's content and
+    /// the previous content of `path` is removed when the guard is dropped.
+    ///
+    /// On Linux this uses `renameat2(RENAME_EXCHANGE)` and is atomic. Elsewhere, or on file
+    /// systems which don't support it, the exchange is done with three renames through a
+    /// scratch name, so `path` briefly doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    ///
+    /// let dir = Temp::new_dir().unwrap();
+    /// let config = dir.join("app.conf");
+    /// fs::write(&config, "old").unwrap();
+    ///
+    /// let new_config = Temp::new_file_in(&dir).unwrap();
+    /// fs::write(&new_config, "new").unwrap();
+    ///
+    /// let mut old_config = new_config;
+    /// old_config.swap_with(&config).unwrap();
+    /// assert_eq!(fs::read_to_string(&config).unwrap(), "new");
+    /// assert_eq!(fs::read_to_string(&old_config).unwrap(), "old");
+    /// ```
+    pub fn swap_with<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        require_disk(self.fs(), "swap_with")?;
+        let file_type = fs::symlink_metadata(path)?.file_type();
+
+        match sys::rename_exchange(&self.path, path) {
+            Err(ref e) if sys::is_unsupported(e) => self.swap_with_renames(path)?,
+            result => result?,
+        }
+
+        self.kind = if file_type.is_dir() {
+            Kind::Dir
+        } else if file_type.is_file() {
+            Kind::File
+        } else {
+            Kind::Path
+        };
+        Ok(())
+    }
+
+    fn swap_with_renames(&self, path: &Path) -> io::Result<()> {
+        let name = path.file_name().unwrap_or_else(|| OsStr::new("swap"));
+        let prefix = format!("{}.swap-", name.to_string_lossy());
+        let scratch = ::create_path_with_ext_in(path.with_file_name(""), &prefix, None);
+
+        fs::rename(path, &scratch)?;
+        if let Err(e) = fs::rename(&self.path, path) {
+            let _result = fs::rename(&scratch, path);
+            return Err(e);
+        }
+        fs::rename(&scratch, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    #[cfg(unix)]
+    fn read_only_dirs_are_copied() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let src = Temp::new_dir().unwrap();
+        fs::create_dir(src.join("fixture")).unwrap();
+        fs::write(src.join("fixture").join("input"), b"data").unwrap();
+        fs::set_permissions(src.join("fixture"), fs::Permissions::from_mode(0o555)).unwrap();
+
+        let copy = Temp::dir_from(&src).unwrap();
+        let copied = copy.join("fixture");
+        assert_eq!(fs::read(copied.join("input")).unwrap(), b"data");
+        let mode = fs::metadata(&copied).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o555);
+
+        // Writable again, so both can be removed without root.
+        for dir in &[src.join("fixture"), copied] {
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[test]
+    fn renamed_file_is_removed_on_drop() {
+        let mut temp = Temp::new_file().unwrap();
+        let old_path = temp.to_path_buf();
+
+        temp.rename_within("renamed.txt").unwrap();
+        let new_path = temp.to_path_buf();
+        assert_eq!(new_path.parent(), old_path.parent());
+        assert!(!old_path.exists());
+        assert!(new_path.is_file());
+
+        drop(temp);
+        assert!(!new_path.exists());
+    }
+
+    #[test]
+    fn moved_dir_is_removed_on_drop() {
+        let target = Temp::new_dir().unwrap();
+        let mut temp = Temp::new_dir().unwrap();
+        fs::write(temp.join("file"), b"data").unwrap();
+        let old_path = temp.to_path_buf();
+
+        temp.move_into(&target).unwrap();
+        assert!(temp.starts_with(&target));
+        assert_eq!(temp.file_name(), old_path.file_name());
+        assert!(temp.join("file").is_file());
+        assert!(!old_path.exists());
+
+        let new_path = temp.to_path_buf();
+        drop(temp);
+        assert!(!new_path.exists());
+    }
+
+    #[test]
+    fn copy_recursive_copies_tree() {
+        let from = Temp::new_dir().unwrap();
+        fs::create_dir(from.join("sub")).unwrap();
+        fs::write(from.join("sub").join("file"), b"data").unwrap();
+
+        let to = Temp::new_path();
+        copy_recursive(&from, &to).unwrap();
+        assert_eq!(fs::read(to.join("sub").join("file")).unwrap(), b"data");
+    }
+
+    #[test]
+    fn swap_with_exchanges_content() {
+        let dir = Temp::new_dir().unwrap();
+        let target = dir.join("target");
+        fs::write(&target, b"old").unwrap();
+
+        let mut temp = Temp::new_file_in(&dir).unwrap();
+        fs::write(&temp, b"new").unwrap();
+        temp.swap_with(&target).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+        assert_eq!(fs::read(&temp).unwrap(), b"old");
+
+        let old = temp.to_path_buf();
+        drop(temp);
+        assert!(!old.exists());
+        assert!(target.exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn swap_with_renames_exchanges_content() {
+        let dir = Temp::new_dir().unwrap();
+        let target = dir.join("target");
+        fs::write(&target, b"old").unwrap();
+
+        let temp = Temp::new_file_in(&dir).unwrap();
+        fs::write(&temp, b"new").unwrap();
+        temp.swap_with_renames(&target).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+        assert_eq!(fs::read(&temp).unwrap(), b"old");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn persist_to_replaces_target() {
+        let dir = Temp::new_dir().unwrap();
+        let target = dir.join("target");
+        fs::write(&target, b"old").unwrap();
+
+        let temp = Temp::new_file_near(&target).unwrap();
+        assert!(temp.starts_with(&*dir));
+        assert!(temp.same_device(&target).unwrap());
+        fs::write(&temp, b"new").unwrap();
+
+        let temp_path = temp.to_path_buf();
+        assert_eq!(temp.persist_to(&target).unwrap(), target);
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+        assert!(!temp_path.exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn copy_file_never_follows_a_planted_symlink() {
+        let dir = Temp::new_dir().unwrap();
+        let (src, victim, planted) = (dir.join("src"), dir.join("victim"), dir.join("copy"));
+        fs::write(&src, b"data").unwrap();
+        fs::write(&victim, b"victim").unwrap();
+        ::std::os::unix::fs::symlink(&victim, &planted).unwrap();
+
+        let e = sys::copy_file(&src, &planted).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&victim).unwrap(), b"victim");
+    }
+
+    #[test]
+    fn dir_from_copies_tree() {
+        let src = Temp::new_dir().unwrap();
+        fs::create_dir(src.join("sub")).unwrap();
+        fs::write(src.join("sub").join("file"), b"data").unwrap();
+
+        let copy = Temp::dir_from(&src).unwrap();
+        assert_eq!(fs::read(copy.join("sub").join("file")).unwrap(), b"data");
+
+        let path = copy.to_path_buf();
+        drop(copy);
+        assert!(!path.exists());
+        assert!(src.join("sub").join("file").exists());
+    }
+
+    #[test]
+    fn copy_from_is_removed_on_drop() {
+        let src = Temp::new_file().unwrap();
+        fs::write(&src, b"data").unwrap();
+
+        let copy = Temp::copy_from(&src).unwrap();
+        assert_eq!(fs::read(&copy).unwrap(), b"data");
+
+        let path = copy.to_path_buf();
+        drop(copy);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn rejects_names_with_components() {
+        let dir = Temp::new_dir().unwrap();
+        let mut temp = Temp::new_file_in(&dir).unwrap();
+
+        for name in &["a/b", "..", ".", ""] {
+            match temp.rename_within(name) {
+                Err(ref e) if e.kind() == io::ErrorKind::InvalidInput => (),
+                _ => panic!("{:?} was accepted", name),
+            }
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn alias_is_a_hard_link_with_its_own_guard() {
+        let original = Temp::new_file().unwrap();
+        fs::write(&original, b"blob").unwrap();
+        let alias = original.alias().unwrap();
+        assert_ne!(alias.path(), original.path());
+        assert_eq!(alias.path().parent(), original.path().parent());
+
+        fs::write(&original, b"changed").unwrap();
+        assert_eq!(fs::read(&alias).unwrap(), b"changed");
+        let path = alias.to_path_buf();
+        drop(alias);
+        assert!(!path.exists() && original.exists());
+
+        let dir = Temp::new_dir().unwrap();
+        assert_eq!(dir.alias().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,529 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::cell::RefCell;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+#[cfg(unix)]
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Component, Path, PathBuf};
+use std::process;
+use std::sync::{Mutex, MutexGuard};
+
+use create_path_with_ext_in;
+use diagnostics::RootSource;
+use sys;
+
+struct Roots {
+    candidates: Vec<PathBuf>,
+    // `None` until the candidates are probed, then the first usable one, if any.
+    chosen: Option<Option<PathBuf>>,
+    runs: usize,
+    run_dir: Option<PathBuf>,
+    fallback: Option<PathBuf>,
+    // The system temp directory last probed because of `fallback`, and the directory to use.
+    fallback_probe: Option<(PathBuf, PathBuf, RootSource)>,
+    secure_system_temp: bool,
+    symlink_free: bool,
+}
+
+static ROOTS: Mutex<Roots> = Mutex::new(Roots {
+    candidates: Vec::new(),
+    chosen: None,
+    runs: 0,
+    run_dir: None,
+    fallback: None,
+    fallback_probe: None,
+    secure_system_temp: false,
+    symlink_free: false,
+});
+
+thread_local! {
+    static THREAD_ROOT: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+fn roots() -> MutexGuard<'static, Roots> {
+    ROOTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Set an ordered list of directories to create temporaries in when no directory is given with
+/// [`Builder::in_dir`](struct.Builder.html#method.in_dir).
+///
+/// The first candidate that exists, or can be created, and is writable is used, falling back to
+/// [`env::temp_dir`](https://doc.rust-lang.org/std/env/fn.temp_dir.html) if none are. The choice
+/// is made once, on first use, and is reported by [`root`](fn.root.html). An empty list
+/// restores the default.
+///
+/// # Examples
+///
+/// ```
+/// use std::env;
+///
+/// let candidates = env::var_os("XDG_RUNTIME_DIR")
+///     .into_iter()
+///     .map(Into::into)
+///     .chain(vec!["/dev/shm".into(), env::temp_dir()]);
+/// mktemp::set_roots(candidates);
+///
+/// println!("temporaries are created in {}", mktemp::root().display());
+/// # mktemp::set_roots(Vec::<std::path::PathBuf>::new());
+/// ```
+pub fn set_roots<I>(candidates: I)
+where
+    I: IntoIterator,
+    I::Item: Into<PathBuf>,
+{
+    let mut roots = roots();
+    roots.candidates = candidates.into_iter().map(Into::into).collect();
+    roots.chosen = None;
+}
+
+/// Fall back to a `.tmp` directory in `dir` when the system temp directory is unusable, e.g.
+/// on a read-only container file system or with a broken `TMPDIR`, instead of failing to
+/// create temporaries. `None`, the default, turns the fallback off.
+///
+/// The system temp directory is only checked once, the first time it is needed.
+///
+/// # Examples
+///
+/// ```
+/// use std::env;
+///
+/// mktemp::set_local_fallback(Some(env::current_dir().unwrap()));
+/// ```
+pub fn set_local_fallback(dir: Option<PathBuf>) {
+    let mut roots = roots();
+    roots.fallback = dir;
+    roots.fallback_probe = None;
+}
+
+/// On Windows 11 and later, find the system temp directory with `GetTempPath2W` rather than
+/// [`env::temp_dir`](https://doc.rust-lang.org/std/env/fn.temp_dir.html), following Microsoft's
+/// guidance for services: processes running as `SYSTEM` then get the protected
+/// `C:\Windows\SystemTemp` instead of a directory other users may be able to write to. Has no
+/// effect on older versions of Windows or on other platforms.
+///
+/// # Examples
+///
+/// ```
+/// mktemp::set_secure_system_temp(true);
+/// ```
+pub fn set_secure_system_temp(secure: bool) {
+    let mut roots = roots();
+    roots.secure_system_temp = secure;
+    roots.fallback_probe = None;
+}
+
+/// Resolve the directory temporaries are created in by default component by component before
+/// every creation, failing with `PermissionDenied` if it goes through a symlink owned by a user
+/// other than root or the current user, and creating temporaries at the resolved path. This
+/// keeps a hardened daemon from being redirected into a directory of an attacker's choosing by
+/// a symlink in, e.g., `TMPDIR`. On platforms other than Unix every symlink is rejected.
+///
+/// Directories given with [`Builder::in_dir`](struct.Builder.html#method.in_dir) are used as
+/// they are.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::Temp;
+///
+/// mktemp::set_symlink_free_root(true);
+/// match Temp::new_file() {
+///     Ok(temp_file) => assert_eq!(temp_file.canonicalize().unwrap(), temp_file.path()),
+///     Err(e) => eprintln!("refusing to use the temp directory: {}", e),
+/// }
+/// ```
+pub fn set_symlink_free_root(symlink_free: bool) {
+    roots().symlink_free = symlink_free;
+}
+
+/// Run `f` with temporaries created on this thread defaulting to `root`, restoring the previous
+/// default afterwards, even if `f` panics. Other threads are unaffected, so tests running in
+/// parallel can each keep their temporaries apart.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::Temp;
+///
+/// let test_root = Temp::new_dir().unwrap();
+/// mktemp::with_root(&test_root, || {
+///     let temp_file = Temp::new_file().unwrap();
+///     assert!(temp_file.starts_with(&test_root));
+/// });
+/// ```
+pub fn with_root<P, F, R>(root: P, f: F) -> R
+where
+    P: AsRef<Path>,
+    F: FnOnce() -> R,
+{
+    struct Restore(Option<PathBuf>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            THREAD_ROOT.with(|root| *root.borrow_mut() = previous);
+        }
+    }
+
+    let previous =
+        THREAD_ROOT.with(|current| current.borrow_mut().replace(root.as_ref().to_path_buf()));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// The directory temporaries are created in by default: the innermost
+/// [`with_root`](fn.with_root.html) on this thread, otherwise the [`RunDir`](struct.RunDir.html)
+/// if one is alive, otherwise the root chosen from [`set_roots`](fn.set_roots.html).
+pub fn root() -> PathBuf {
+    resolve().0
+}
+
+/// The default directory and where it came from.
+pub(crate) fn resolve() -> (PathBuf, RootSource) {
+    if let Some(root) = THREAD_ROOT.with(|root| root.borrow().clone()) {
+        return (root, RootSource::ThreadOverride);
+    }
+
+    let mut roots = roots();
+    let (base, source) = match roots.chosen() {
+        Some(chosen) => (chosen, RootSource::Candidate),
+        None => roots.system(),
+    };
+    if roots.runs == 0 {
+        return (base, source);
+    }
+    if roots.run_dir.is_none() {
+        roots.run_dir = create_run_dir(base.clone()).ok();
+    }
+
+    match roots.run_dir {
+        Some(ref run_dir) => (run_dir.clone(), RootSource::RunDir),
+        None => (base, source),
+    }
+}
+
+/// The default directory for a backend that doesn't create temporaries on disk: the
+/// [`with_root`](fn.with_root.html) override, the first candidate or the system temp directory,
+/// none of which is probed or created.
+pub(crate) fn unprobed_root() -> PathBuf {
+    if let Some(root) = THREAD_ROOT.with(|root| root.borrow().clone()) {
+        return root;
+    }
+    match roots().candidates.first() {
+        Some(candidate) => candidate.clone(),
+        None => env::temp_dir(),
+    }
+}
+
+/// The default directory, resolved without untrusted symlinks if
+/// [`set_symlink_free_root`](fn.set_symlink_free_root.html) is on.
+pub(crate) fn checked_root() -> io::Result<PathBuf> {
+    let root = root();
+    if roots().symlink_free {
+        resolve_symlink_free(&root)
+    } else {
+        Ok(root)
+    }
+}
+
+/// Resolve `path` to an absolute path without symlinks, failing if it goes through a symlink
+/// that isn't trusted.
+fn resolve_symlink_free(path: &Path) -> io::Result<PathBuf> {
+    // The same limit as Linux's ELOOP.
+    const MAX_LINKS: usize = 40;
+
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir()?.join(path)
+    };
+
+    // The components still to resolve, in reverse order, so the next one can be popped.
+    let mut pending: Vec<OsString> = Vec::new();
+    push_components(&mut pending, &path);
+    let mut resolved = PathBuf::new();
+    let mut links = 0;
+    while let Some(component) = pending.pop() {
+        if component == ".." {
+            resolved.pop();
+            continue;
+        }
+        resolved.push(&component);
+
+        let metadata = fs::symlink_metadata(&resolved)?;
+        if !metadata.file_type().is_symlink() {
+            continue;
+        }
+        if !is_trusted_link(&metadata) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "{} is a symlink owned by another user in the temp directory {}",
+                    resolved.display(),
+                    path.display()
+                ),
+            ));
+        }
+        links += 1;
+        if links > MAX_LINKS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("too many levels of symlinks in {}", path.display()),
+            ));
+        }
+        let target = fs::read_link(&resolved)?;
+        resolved.pop();
+        push_components(&mut pending, &target);
+    }
+    Ok(resolved)
+}
+
+/// Queue the components of `path` to be resolved before those already pending.
+fn push_components(pending: &mut Vec<OsString>, path: &Path) {
+    let start = pending.len();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => pending.push("..".into()),
+            component => pending.push(component.as_os_str().to_os_string()),
+        }
+    }
+    pending[start..].reverse();
+}
+
+#[cfg(unix)]
+fn is_trusted_link(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    let owner = metadata.uid();
+    owner == 0 || owner == unsafe { ::libc::geteuid() }
+}
+
+#[cfg(not(unix))]
+fn is_trusted_link(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// The candidates set with [`set_roots`](fn.set_roots.html).
+pub(crate) fn candidates() -> Vec<PathBuf> {
+    roots().candidates.clone()
+}
+
+/// Where `env::temp_dir` gets the directory from, which mirrors the standard library.
+fn temp_dir_source() -> RootSource {
+    for &name in TEMP_DIR_VARS {
+        if env::var_os(name).map_or(false, |value| !value.is_empty()) {
+            return RootSource::EnvVar(name);
+        }
+    }
+    RootSource::PlatformDefault
+}
+
+#[cfg(windows)]
+pub(crate) const TEMP_DIR_VARS: &[&str] = &["TMP", "TEMP", "USERPROFILE"];
+#[cfg(not(windows))]
+pub(crate) const TEMP_DIR_VARS: &[&str] = &["TMPDIR"];
+
+impl Roots {
+    /// The system temp directory, or the local fallback if it is unusable.
+    fn system(&mut self) -> (PathBuf, RootSource) {
+        let system = if self.secure_system_temp {
+            sys::temp_path2().unwrap_or_else(env::temp_dir)
+        } else {
+            env::temp_dir()
+        };
+        let fallback = match self.fallback {
+            Some(ref fallback) => fallback.clone(),
+            None => return (system, temp_dir_source()),
+        };
+
+        match self.fallback_probe {
+            Some((ref probed, ref dir, source)) if *probed == system => (dir.clone(), source),
+            _ => {
+                let (dir, source) = match local_fallback(&system, &fallback) {
+                    Some(local) => (local, RootSource::LocalFallback),
+                    None => (system.clone(), temp_dir_source()),
+                };
+                self.fallback_probe = Some((system, dir.clone(), source));
+                (dir, source)
+            }
+        }
+    }
+
+    fn chosen(&mut self) -> Option<PathBuf> {
+        if self.candidates.is_empty() {
+            return None;
+        }
+        if self.chosen.is_none() {
+            let chosen = self
+                .candidates
+                .iter()
+                .find(|candidate| is_usable(candidate))
+                .cloned();
+            self.chosen = Some(chosen);
+        }
+
+        self.chosen.clone().unwrap()
+    }
+}
+
+/// Collects every temporary created without an explicit directory into a single directory for
+/// this run of the program, which is created on first use and removed with everything in it
+/// when the `RunDir` is dropped.
+///
+/// This keeps the temp directory tidy even if some temporaries are leaked, and makes all the
+/// artifacts of a run easy to find. Create it at the start of `main` so it is dropped on the
+/// way out; Rust doesn't run destructors for statics at exit. While more than one `RunDir` is
+/// alive they share the directory, which is removed when the last one is dropped.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::{RunDir, Temp};
+///
+/// let run = RunDir::new();
+/// let temp_file = Temp::new_file().unwrap().release();
+///
+/// let run_dir = run.path().unwrap();
+/// assert!(temp_file.starts_with(&run_dir));
+///
+/// drop(run);
+/// assert!(!run_dir.exists());
+/// ```
+#[derive(Debug)]
+pub struct RunDir {
+    _private: (),
+}
+
+impl RunDir {
+    /// Start collecting temporaries into a run directory.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        roots().runs += 1;
+        RunDir { _private: () }
+    }
+
+    /// The run directory, if a temporary has been created in it yet.
+    pub fn path(&self) -> Option<PathBuf> {
+        roots().run_dir.clone()
+    }
+}
+
+impl Drop for RunDir {
+    fn drop(&mut self) {
+        let mut roots = roots();
+        roots.runs -= 1;
+        if roots.runs == 0 {
+            if let Some(dir) = roots.run_dir.take() {
+                let _result = fs::remove_dir_all(dir);
+            }
+        }
+    }
+}
+
+fn create_run_dir(base: PathBuf) -> io::Result<PathBuf> {
+    let dir = create_path_with_ext_in(base, &format!("mktemp-run-{}-", process::id()), None);
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder.create(&dir)?;
+    Ok(dir)
+}
+
+/// The `.tmp` directory in `fallback`, if `system` is unusable and it can be created.
+fn local_fallback(system: &Path, fallback: &Path) -> Option<PathBuf> {
+    if probe(system).is_ok() {
+        return None;
+    }
+
+    let local = fallback.join(".tmp");
+    if is_usable(&local) {
+        Some(local)
+    } else {
+        None
+    }
+}
+
+fn is_usable(dir: &Path) -> bool {
+    fs::create_dir_all(dir).is_ok() && probe(dir).is_ok()
+}
+
+/// Check that a file can be created and removed in `dir`.
+pub(crate) fn probe(dir: &Path) -> io::Result<()> {
+    let probe = create_path_with_ext_in(dir.to_path_buf(), ".probe-", None);
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)?;
+    fs::remove_file(&probe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Temp;
+
+    #[test]
+    #[cfg(unix)]
+    fn symlink_free_resolution_follows_own_links() {
+        let dir = Temp::new_dir().unwrap();
+        fs::create_dir(dir.join("real")).unwrap();
+        ::std::os::unix::fs::symlink("real", dir.join("link")).unwrap();
+        ::std::os::unix::fs::symlink(dir.join("link"), dir.join("absolute")).unwrap();
+
+        let real = fs::canonicalize(dir.join("real")).unwrap();
+        assert_eq!(resolve_symlink_free(&dir.join("link")).unwrap(), real);
+        let through = dir.join("absolute").join("..").join("real");
+        assert_eq!(resolve_symlink_free(&through).unwrap(), real);
+    }
+
+    #[test]
+    fn first_usable_candidate_is_chosen() {
+        let file = Temp::new_file().unwrap();
+        // A local list rather than the crate-wide one, which other tests use concurrently.
+        let mut roots = Roots {
+            // A directory can't be created below a regular file.
+            candidates: vec![file.join("unusable"), env::temp_dir()],
+            chosen: None,
+            runs: 0,
+            run_dir: None,
+            fallback: None,
+            fallback_probe: None,
+            secure_system_temp: false,
+            symlink_free: false,
+        };
+
+        assert_eq!(roots.chosen(), Some(env::temp_dir()));
+        assert!(!file.join("unusable").exists());
+    }
+
+    #[test]
+    fn local_fallback_only_when_system_is_unusable() {
+        let system = Temp::new_dir().unwrap();
+        let fallback = Temp::new_dir().unwrap();
+        assert_eq!(local_fallback(&system, &fallback), None);
+
+        let local = local_fallback(&system.join("missing"), &fallback).unwrap();
+        assert_eq!(local, fallback.join(".tmp"));
+        assert!(local.is_dir());
+    }
+
+    #[test]
+    fn with_root_nests_and_restores() {
+        let outer = Temp::new_dir().unwrap();
+        let inner = Temp::new_dir().unwrap();
+
+        with_root(&outer, || {
+            with_root(&inner, || assert_eq!(root(), inner.to_path_buf()));
+            assert!(Temp::new_file().unwrap().starts_with(&outer));
+
+            let other = ::std::thread::spawn(root).join().unwrap();
+            assert_ne!(other, outer.to_path_buf());
+        });
+        assert_ne!(root(), outer.to_path_buf());
+    }
+}
@@ -0,0 +1,233 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+
+use path_error::annotate;
+use {Builder, Temp};
+
+// Passed through from the parent, so the program under test can still be found and, on
+// Windows, start at all.
+const INHERITED: &[&str] = &["PATH", "SystemRoot"];
+
+const XDG_DIRS: &[(&str, &str)] = &[
+    ("XDG_CONFIG_HOME", "home/.config"),
+    ("XDG_DATA_HOME", "home/.local/share"),
+    ("XDG_STATE_HOME", "home/.local/state"),
+    ("XDG_CACHE_HOME", "home/.cache"),
+    ("XDG_RUNTIME_DIR", "run"),
+];
+
+/// A disposable environment for integration tests of command line programs: a working
+/// directory, a home directory with the XDG directories in it, a temp directory, and files
+/// capturing the output of each run, all in one temporary directory removed on drop.
+///
+/// [`exec`](#method.exec) runs a command in the working directory with an environment made only
+/// of `PATH`, the variables pointing into the sandbox (`HOME`, `USERPROFILE`, `TMPDIR`, `TEMP`,
+/// `TMP` and `XDG_*`), and those set with [`env`](#method.env), so nothing leaks in from the
+/// environment of the test or out into the real home directory. The environment of the test
+/// process itself is left alone.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mktemp::Sandbox;
+/// use std::fs;
+/// use std::process::Command;
+///
+/// let mut sandbox = Sandbox::new().unwrap();
+/// sandbox.env("RUST_LOG", "debug");
+/// fs::write(sandbox.work_dir().join("input.txt"), "hello").unwrap();
+///
+/// let run = sandbox
+///     .exec(Command::new("tool-under-test").arg("input.txt"))
+///     .unwrap();
+/// assert!(run.status().success());
+/// assert_eq!(run.read_stdout().unwrap(), "HELLO\n");
+/// assert!(sandbox.home_dir().join(".config/tool/state.toml").exists());
+/// ```
+#[derive(Debug)]
+pub struct Sandbox {
+    root: Temp,
+    vars: Vec<(OsString, OsString)>,
+}
+
+impl Sandbox {
+    /// Create the sandbox in the system temp directory.
+    #[track_caller]
+    pub fn new() -> io::Result<Self> {
+        Self::with_builder(&Builder::new())
+    }
+
+    /// Create the sandbox using `builder`.
+    #[track_caller]
+    pub fn with_builder(builder: &Builder) -> io::Result<Self> {
+        let root = builder.dir()?;
+        for dir in ["work", "tmp", "home", "output"]
+            .iter()
+            .cloned()
+            .chain(XDG_DIRS.iter().map(|&(_, dir)| dir))
+        {
+            let path = root.join(dir);
+            annotate(fs::create_dir_all(&path), "create directory", &path)?;
+        }
+        Ok(Sandbox {
+            root,
+            vars: Vec::new(),
+        })
+    }
+
+    /// The directory holding everything in the sandbox.
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// The working directory of the commands run.
+    pub fn work_dir(&self) -> PathBuf {
+        self.root.join("work")
+    }
+
+    /// The home directory, `HOME` and `USERPROFILE`.
+    pub fn home_dir(&self) -> PathBuf {
+        self.root.join("home")
+    }
+
+    /// The temp directory, `TMPDIR`, `TEMP` and `TMP`.
+    pub fn tmp_dir(&self) -> PathBuf {
+        self.root.join("tmp")
+    }
+
+    /// Set an environment variable for the commands run, overriding the sandbox's own.
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(&mut self, key: K, value: V) -> &mut Self {
+        self.vars
+            .push((key.as_ref().to_os_string(), value.as_ref().to_os_string()));
+        self
+    }
+
+    /// Run `command` in the sandbox with no input, wait for it to exit and return its status
+    /// and captured output. A failure to start the command is returned as an error, a failure
+    /// of the command is only in the status.
+    pub fn exec(&self, command: &mut Command) -> io::Result<SandboxOutput> {
+        let output_dir = self.root.join("output");
+        let stdout = Builder::new()
+            .in_dir(&output_dir)
+            .prefix("stdout-")
+            .file()?;
+        let stderr = Builder::new()
+            .in_dir(&output_dir)
+            .prefix("stderr-")
+            .file()?;
+
+        command
+            .current_dir(self.work_dir())
+            .env_clear()
+            .stdin(Stdio::null())
+            .stdout(annotate(File::create(&stdout), "open", &stdout)?)
+            .stderr(annotate(File::create(&stderr), "open", &stderr)?);
+        for name in INHERITED {
+            if let Some(value) = env::var_os(name) {
+                command.env(name, value);
+            }
+        }
+        for name in &["HOME", "USERPROFILE"] {
+            command.env(name, self.home_dir());
+        }
+        for name in &["TMPDIR", "TEMP", "TMP"] {
+            command.env(name, self.tmp_dir());
+        }
+        for &(name, dir) in XDG_DIRS {
+            command.env(name, self.root.join(dir));
+        }
+        for (key, value) in &self.vars {
+            command.env(key, value);
+        }
+
+        let status = command.status()?;
+        Ok(SandboxOutput {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+impl AsRef<Path> for Sandbox {
+    fn as_ref(&self) -> &Path {
+        &self.root
+    }
+}
+
+/// The result of [`Sandbox::exec`](struct.Sandbox.html#method.exec), with the output kept in
+/// temporary files, so large output doesn't have to fit in memory.
+#[derive(Debug)]
+pub struct SandboxOutput {
+    status: ExitStatus,
+    stdout: Temp,
+    stderr: Temp,
+}
+
+impl SandboxOutput {
+    /// The exit status.
+    pub fn status(&self) -> ExitStatus {
+        self.status
+    }
+
+    /// The file holding the standard output.
+    pub fn stdout(&self) -> &Temp {
+        &self.stdout
+    }
+
+    /// The file holding the standard error.
+    pub fn stderr(&self) -> &Temp {
+        &self.stderr
+    }
+
+    /// Read the standard output, which must be UTF-8.
+    pub fn read_stdout(&self) -> io::Result<String> {
+        annotate(fs::read_to_string(&self.stdout), "read", &self.stdout)
+    }
+
+    /// Read the standard error, which must be UTF-8.
+    pub fn read_stderr(&self) -> io::Result<String> {
+        annotate(fs::read_to_string(&self.stderr), "read", &self.stderr)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_runs_hermetically() {
+        let mut sandbox = Sandbox::new().unwrap();
+        sandbox.env("GREETING", "hi");
+        let run = sandbox
+            .exec(Command::new("sh").arg("-c").arg(
+                "pwd; echo \"$HOME $XDG_CONFIG_HOME $TMPDIR $GREETING ${CARGO:-unset}\"; \
+                 echo oops >&2; exit 3",
+            ))
+            .unwrap();
+
+        assert_eq!(run.status().code(), Some(3));
+        assert_eq!(run.read_stderr().unwrap(), "oops\n");
+        let stdout = run.read_stdout().unwrap();
+        let mut lines = stdout.lines();
+        assert_eq!(
+            Path::new(lines.next().unwrap()).canonicalize().unwrap(),
+            sandbox.work_dir().canonicalize().unwrap()
+        );
+        let expected = format!(
+            "{} {} {} hi unset",
+            sandbox.home_dir().display(),
+            sandbox.home_dir().join(".config").display(),
+            sandbox.tmp_dir().display()
+        );
+        assert_eq!(lines.next().unwrap(), expected);
+        assert!(run.stdout().starts_with(sandbox.path()));
+    }
+}
@@ -0,0 +1,100 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::path::PathBuf;
+
+use typed::TempDir;
+
+// The limit of most file systems, in bytes, less one for the `_` prefix of a reserved name.
+const MAX_LEN: usize = 254;
+
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Turn `name` into a single file name that is valid on every platform.
+pub(crate) fn sanitize_file_name(name: &str) -> String {
+    let mut safe = String::with_capacity(name.len());
+    for c in name.chars() {
+        let c = match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        };
+        if safe.len() + c.len_utf8() > MAX_LEN {
+            break;
+        }
+        safe.push(c);
+    }
+
+    // Windows drops trailing dots and spaces, so `a.` would be `a`, and `..` nothing at all.
+    while safe.ends_with('.') || safe.ends_with(' ') {
+        safe.pop();
+    }
+    if safe.is_empty() {
+        safe.push('_');
+    }
+    // `CON.txt` is the console too.
+    let stem = safe.split('.').next().unwrap_or("").trim_end();
+    if RESERVED
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        safe.insert(0, '_');
+    }
+    safe
+}
+
+impl TempDir {
+    /// The path of an entry in this directory named after `name`, which may come from a user,
+    /// made into a safe file name: path separators, characters Windows forbids and control
+    /// characters become `_`, trailing dots and spaces are dropped, reserved Windows names like
+    /// `CON` get a `_` prefix and the name is cut to 254 bytes. The result is always a single
+    /// component inside the directory, so `name` can't reach outside it. The entry need not
+    /// exist.
+    ///
+    /// Different names can map to the same file name, e.g. `a/b` and `a:b`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::TempDir;
+    ///
+    /// let uploads = TempDir::new().unwrap();
+    /// assert_eq!(
+    ///     uploads.sanitized_child("../../etc/passwd"),
+    ///     uploads.child(".._.._etc_passwd")
+    /// );
+    /// assert_eq!(uploads.sanitized_child("con.txt"), uploads.child("_con.txt"));
+    /// ```
+    pub fn sanitized_child(&self, name: &str) -> PathBuf {
+        self.child(sanitize_file_name(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_made_safe() {
+        for &(name, safe) in &[
+            ("report.csv", "report.csv"),
+            ("", "_"),
+            ("..", "_"),
+            ("a\\b\u{0}c\nd", "a_b_c_d"),
+            ("notes. . ", "notes"),
+            ("LPT1", "_LPT1"),
+            ("aux .log", "_aux .log"),
+            ("console", "console"),
+        ] {
+            assert_eq!(sanitize_file_name(name), safe);
+        }
+
+        let long = "é".repeat(200);
+        let safe = sanitize_file_name(&long);
+        assert_eq!(safe.len(), 254);
+        assert!(long.starts_with(&safe));
+    }
+}
@@ -0,0 +1,210 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use {Builder, Temp};
+
+/// Owns any number of temporaries and removes all of them, in reverse order of creation, when
+/// it is dropped.
+///
+/// Reverse order means files and directories created inside a scope directory, e.g. with
+/// [`file_in`](#method.file_in), are removed before it. Temporaries handed over with
+/// [`add`](#method.add) that are inside another temporary of the scope are always removed
+/// before it too, whichever was added first, so teardown never trips over a non-empty
+/// directory.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::TempScope;
+///
+/// let paths = {
+///     let mut scope = TempScope::new();
+///     let dir = scope.dir().unwrap();
+///     let file = scope.file().unwrap();
+///     assert!(dir.is_dir() && file.is_file());
+///     (dir, file)
+/// };
+/// assert!(!paths.0.exists() && !paths.1.exists());
+/// ```
+#[derive(Default)]
+pub struct TempScope {
+    builder: Builder,
+    temps: Vec<Temp>,
+}
+
+impl TempScope {
+    /// Create an empty scope which creates temporaries with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty scope which creates temporaries using `builder`.
+    pub fn with_builder(builder: Builder) -> Self {
+        TempScope {
+            builder,
+            temps: Vec::new(),
+        }
+    }
+
+    /// Create a temporary file owned by the scope.
+    #[track_caller]
+    pub fn file(&mut self) -> io::Result<PathBuf> {
+        let temp = self.builder.file()?;
+        Ok(self.add(temp))
+    }
+
+    /// Create a temporary directory owned by the scope.
+    #[track_caller]
+    pub fn dir(&mut self) -> io::Result<PathBuf> {
+        let temp = self.builder.dir()?;
+        Ok(self.add(temp))
+    }
+
+    /// Create a temporary file owned by the scope in `dir`, usually a directory of the scope.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::TempScope;
+    ///
+    /// let mut scope = TempScope::new();
+    /// let dir = scope.dir().unwrap();
+    /// let file = scope.file_in(&dir).unwrap();
+    /// assert!(file.starts_with(&dir));
+    /// ```
+    #[track_caller]
+    pub fn file_in<P: AsRef<Path>>(&mut self, dir: P) -> io::Result<PathBuf> {
+        let temp = self.builder.clone().in_dir(dir).file()?;
+        Ok(self.add(temp))
+    }
+
+    /// Create a temporary directory owned by the scope in `dir`, see
+    /// [`file_in`](#method.file_in).
+    #[track_caller]
+    pub fn dir_in<P: AsRef<Path>>(&mut self, dir: P) -> io::Result<PathBuf> {
+        let temp = self.builder.clone().in_dir(dir).dir()?;
+        Ok(self.add(temp))
+    }
+
+    /// Create an uninitialized temporary path owned by the scope.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the default directory is rejected, see
+    /// [`Builder::path`](struct.Builder.html#method.path).
+    #[track_caller]
+    pub fn path(&mut self) -> PathBuf {
+        let temp = self.builder.path();
+        self.add(temp)
+    }
+
+    /// Hand an existing temporary over to the scope.
+    pub fn add(&mut self, temp: Temp) -> PathBuf {
+        let path = temp.to_path_buf();
+        self.temps.push(temp);
+        path
+    }
+
+    /// The number of temporaries owned by the scope.
+    pub fn len(&self) -> usize {
+        self.temps.len()
+    }
+
+    /// Whether the scope owns no temporaries.
+    pub fn is_empty(&self) -> bool {
+        self.temps.is_empty()
+    }
+}
+
+impl fmt::Debug for TempScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TempScope")
+            .field("temps", &self.temps)
+            .finish()
+    }
+}
+
+impl Drop for TempScope {
+    fn drop(&mut self) {
+        // A `Vec` drops its elements front to back, remove the newest temporaries first instead.
+        while let Some(temp) = self.temps.pop() {
+            remove_with_descendants(&mut self.temps, temp);
+        }
+    }
+}
+
+/// Remove `temp`, after any of `temps` inside it, newest first.
+fn remove_with_descendants(temps: &mut Vec<Temp>, temp: Temp) {
+    let mut i = temps.len();
+    while i > 0 {
+        i -= 1;
+        if temps[i].path.starts_with(&temp.path) {
+            let descendant = temps.remove(i);
+            remove_with_descendants(temps, descendant);
+            i = i.min(temps.len());
+        }
+    }
+
+    drop(temp);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::Recorder;
+    use std::sync::Arc;
+
+    #[test]
+    fn removes_in_reverse_order() {
+        let fs = Arc::new(Recorder::default());
+        let mut builder = Builder::new();
+        builder.fs(fs.clone());
+
+        let mut scope = TempScope::with_builder(builder);
+        let first = scope.dir().unwrap();
+        let second = scope.file().unwrap();
+        let third = scope.path();
+        assert_eq!(scope.len(), 3);
+
+        drop(scope);
+        assert_eq!(*fs.removed.lock().unwrap(), vec![third, second, first]);
+    }
+
+    #[test]
+    fn removes_children_before_parents() {
+        let fs = Arc::new(Recorder::default());
+        let mut builder = Builder::new();
+        builder.fs(fs.clone());
+
+        let mut scope = TempScope::with_builder(builder.clone());
+        let unrelated = scope.file().unwrap();
+        let dir = Temp::new_dir().unwrap();
+        let child = scope.add(builder.clone().in_dir(&dir).dir().unwrap());
+        let grandchild = scope.file_in(&child).unwrap();
+        let dir = scope.add(dir);
+        let later = scope.file().unwrap();
+
+        drop(scope);
+        assert_eq!(
+            *fs.removed.lock().unwrap(),
+            vec![later, grandchild, child, unrelated]
+        );
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn uses_builder() {
+        let parent = Temp::new_dir().unwrap();
+        let mut builder = Builder::new();
+        builder.in_dir(&parent).extension("log");
+
+        let mut scope = TempScope::with_builder(builder);
+        let file = scope.file().unwrap();
+        assert!(file.starts_with(&parent));
+        assert_eq!(file.extension().unwrap(), "log");
+    }
+}
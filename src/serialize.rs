@@ -0,0 +1,105 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fs;
+use std::io;
+
+use serde_crate::Serialize;
+use Temp;
+
+fn invalid_data<E: ::std::error::Error + Send + Sync + 'static>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Helpers for writing fixtures, enabled with the `serde` feature. Each replaces the contents
+/// of the temporary file.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::Temp;
+/// use std::collections::BTreeMap;
+/// use std::fs;
+///
+/// let mut config = BTreeMap::new();
+/// config.insert("name", "fixture");
+///
+/// let temp_file = Temp::new_file().unwrap();
+/// temp_file.write_json(&config).unwrap();
+/// assert_eq!(fs::read_to_string(&temp_file).unwrap(), r#"{"name":"fixture"}"#);
+/// ```
+impl Temp {
+    /// Serialize `value` into the file as JSON.
+    pub fn write_json<T: Serialize + ?Sized>(&self, value: &T) -> io::Result<()> {
+        let file = fs::File::create(&self.path)?;
+        ::serde_json::to_writer(file, value).map_err(invalid_data)
+    }
+
+    /// Serialize `value` into the file as indented JSON.
+    pub fn write_json_pretty<T: Serialize + ?Sized>(&self, value: &T) -> io::Result<()> {
+        let file = fs::File::create(&self.path)?;
+        ::serde_json::to_writer_pretty(file, value).map_err(invalid_data)
+    }
+
+    /// Serialize `value` into the file as TOML.
+    pub fn write_toml<T: Serialize + ?Sized>(&self, value: &T) -> io::Result<()> {
+        let toml = ::toml::to_string(value).map_err(invalid_data)?;
+        fs::write(&self.path, toml)
+    }
+
+    /// Serialize `value` into the file as TOML, with arrays spread over multiple lines.
+    pub fn write_toml_pretty<T: Serialize + ?Sized>(&self, value: &T) -> io::Result<()> {
+        let toml = ::toml::to_string_pretty(value).map_err(invalid_data)?;
+        fs::write(&self.path, toml)
+    }
+
+    /// Serialize `value` into the file as YAML.
+    pub fn write_yaml<T: Serialize + ?Sized>(&self, value: &T) -> io::Result<()> {
+        let file = fs::File::create(&self.path)?;
+        ::serde_yaml::to_writer(file, value).map_err(invalid_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn fixture() -> BTreeMap<&'static str, Vec<u32>> {
+        let mut fixture = BTreeMap::new();
+        fixture.insert("ports", vec![80, 443]);
+        fixture
+    }
+
+    #[test]
+    fn writes_json() {
+        let temp = Temp::new_file().unwrap();
+        temp.write_json_pretty(&fixture()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&temp).unwrap(),
+            "{\n  \"ports\": [\n    80,\n    443\n  ]\n}"
+        );
+    }
+
+    #[test]
+    fn writes_toml() {
+        let temp = Temp::new_file().unwrap();
+        temp.write_toml(&fixture()).unwrap();
+        assert_eq!(fs::read_to_string(&temp).unwrap(), "ports = [80, 443]\n");
+    }
+
+    #[test]
+    fn writes_yaml() {
+        let temp = Temp::new_file().unwrap();
+        temp.write_yaml(&fixture()).unwrap();
+        assert_eq!(fs::read_to_string(&temp).unwrap(), "ports:\n- 80\n- 443\n");
+    }
+
+    #[test]
+    fn replaces_contents() {
+        let temp = Temp::new_file().unwrap();
+        fs::write(&temp, "a much longer previous content").unwrap();
+        temp.write_json(&1).unwrap();
+        assert_eq!(fs::read_to_string(&temp).unwrap(), "1");
+    }
+}
@@ -0,0 +1,233 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fs;
+use std::io;
+use std::ops;
+use std::path::{self, Path, PathBuf};
+use std::process;
+
+use root::root;
+use sys;
+use uuid::Uuid;
+
+const PARTICIPANTS: &str = ".participants";
+
+/// A temporary directory shared by cooperating processes, e.g. the workers of a process pool,
+/// which is removed when the last of them leaves.
+///
+/// Each participant holds a ticket file in the directory's `.participants` directory for as
+/// long as its `Session` is alive. Leaving removes the ticket, and whoever finds no tickets left
+/// removes the directory. Joining and leaving take turns through the lock file `.{name}.lock`
+/// next to the directory, so nobody joins a session that is being removed. Tickets of processes
+/// that died without leaving are cleared on unix, so a crash doesn't keep the directory around
+/// forever.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::Session;
+/// use std::fs;
+///
+/// let worker = Session::join("render-farm").unwrap();
+/// let other_worker = Session::join("render-farm").unwrap();
+/// assert_eq!(worker.path(), other_worker.path());
+///
+/// fs::write(worker.join("frame-1.png"), b"pixels").unwrap();
+/// drop(worker);
+/// assert!(other_worker.join("frame-1.png").exists());
+///
+/// let path = other_worker.to_path_buf();
+/// drop(other_worker);
+/// assert!(!path.exists());
+/// ```
+#[derive(Debug)]
+pub struct Session {
+    parent: PathBuf,
+    name: String,
+    dir: PathBuf,
+    ticket: PathBuf,
+}
+
+impl Session {
+    /// Join the session `name` in the default directory, see [`root`](fn.root.html), creating
+    /// it if this is the first participant. Processes only share a session if they resolve the
+    /// same default directory, so use [`join_in`](#method.join_in) when a
+    /// [`RunDir`](struct.RunDir.html) is alive.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` contains a path separator.
+    pub fn join(name: &str) -> io::Result<Self> {
+        Self::join_in(root(), name)
+    }
+
+    /// Join the session `name` in `dir`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` contains a path separator.
+    pub fn join_in<P: AsRef<Path>>(dir: P, name: &str) -> io::Result<Self> {
+        assert!(
+            !name.chars().any(path::is_separator),
+            "{:?} contains a path separator",
+            name
+        );
+
+        let parent = dir.as_ref().to_path_buf();
+        let dir = parent.join(name);
+        let participants = dir.join(PARTICIPANTS);
+        let ticket = participants.join(format!("{}-{}", process::id(), Uuid::new_v4().simple()));
+
+        let _lock = sys::lock(&parent, name)?;
+        fs::create_dir_all(&participants)?;
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&ticket)?;
+        Ok(Session {
+            parent,
+            name: name.to_owned(),
+            dir,
+            ticket,
+        })
+    }
+
+    /// The shared directory.
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The number of participants, including this one.
+    pub fn participants(&self) -> io::Result<usize> {
+        Ok(fs::read_dir(self.dir.join(PARTICIPANTS))?.count())
+    }
+
+    fn leave(&self) -> io::Result<()> {
+        let removed = {
+            let _lock = sys::lock(&self.parent, &self.name)?;
+            fs::remove_file(&self.ticket)?;
+
+            let participants = self.dir.join(PARTICIPANTS);
+            clear_dead(&participants)?;
+            if fs::read_dir(&participants)?.next().is_some() {
+                return Ok(());
+            }
+
+            // Move the session out of the way while holding the lock, so a process joining
+            // after it is released starts a new one, and remove it without holding anyone up.
+            let mut removed = self.dir.clone().into_os_string();
+            removed.push(format!(".removed-{}", Uuid::new_v4().simple()));
+            fs::rename(&self.dir, &removed)?;
+            removed
+        };
+        fs::remove_dir_all(&removed)
+    }
+}
+
+/// Remove the tickets of processes that are no longer running.
+#[cfg(unix)]
+fn clear_dead(participants: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(participants)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let pid = match name.to_str().and_then(|name| name.split('-').next()) {
+            Some(pid) => pid.parse::<::libc::pid_t>().ok(),
+            None => None,
+        };
+        let dead = match pid {
+            Some(pid) => {
+                let result = unsafe { ::libc::kill(pid, 0) };
+                result < 0 && io::Error::last_os_error().raw_os_error() == Some(::libc::ESRCH)
+            }
+            None => false,
+        };
+        if dead {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Remove the tickets of processes that are no longer running.
+#[cfg(not(unix))]
+fn clear_dead(_participants: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+impl ops::Deref for Session {
+    type Target = Path;
+    fn deref(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl AsRef<Path> for Session {
+    fn as_ref(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.leave();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use Temp;
+
+    #[test]
+    fn last_participant_removes_session() {
+        let dir = Temp::new_dir().unwrap();
+        let first = Session::join_in(&dir, "pool").unwrap();
+        let second = Session::join_in(&dir, "pool").unwrap();
+        assert_eq!(first.participants().unwrap(), 2);
+
+        fs::write(first.join("shared"), b"data").unwrap();
+        drop(first);
+        assert_eq!(second.participants().unwrap(), 1);
+        assert!(second.join("shared").exists());
+
+        drop(second);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn concurrent_join_and_leave() {
+        let dir = Temp::new_dir().unwrap();
+        let workers: Vec<_> = (0..8)
+            .map(|i| {
+                let dir = dir.to_path_buf();
+                thread::spawn(move || {
+                    for _ in 0..50 {
+                        let session = Session::join_in(&dir, "pool").unwrap();
+                        // The session isn't removed from under a participant.
+                        let scratch = session.join(format!("worker-{}", i));
+                        fs::write(&scratch, b"").unwrap();
+                        fs::remove_file(&scratch).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn tickets_of_dead_processes_are_cleared() {
+        let dir = Temp::new_dir().unwrap();
+        let session = Session::join_in(&dir, "pool").unwrap();
+        // Process IDs don't go this high.
+        fs::write(session.join(PARTICIPANTS).join("2147483646-0"), b"").unwrap();
+
+        drop(session);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+}
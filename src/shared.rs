@@ -0,0 +1,353 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::io;
+use std::ops;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Weak};
+
+use limit;
+use {Builder, Kind, Temp};
+
+/// A reference counted temporary, removed when the last clone is dropped.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::Temp;
+///
+/// let shared = Temp::new_dir().unwrap().into_shared();
+/// let weak = shared.downgrade();
+/// assert!(weak.upgrade().is_some());
+///
+/// drop(shared);
+/// assert!(weak.upgrade().is_none());
+/// assert!(!weak.path().exists());
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedTemp {
+    inner: Arc<Temp>,
+}
+
+impl SharedTemp {
+    /// Share ownership of `temp`.
+    pub fn new(temp: Temp) -> Self {
+        SharedTemp {
+            inner: Arc::new(temp),
+        }
+    }
+
+    /// The temporary, if this is the last handle to it.
+    pub(crate) fn try_unwrap(self) -> Result<Temp, SharedTemp> {
+        Arc::try_unwrap(self.inner).map_err(|inner| SharedTemp { inner })
+    }
+
+    /// Create a weak handle that doesn't keep the temporary alive.
+    pub fn downgrade(&self) -> WeakTemp {
+        WeakTemp {
+            path: self.inner.to_path_buf(),
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// Create a temporary file inside this directory which keeps the directory alive until the
+    /// file is dropped, so the directory can't be removed from under it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    ///
+    /// let dir = Temp::new_dir().unwrap().into_shared();
+    /// let file = dir.child_file().unwrap();
+    /// let dir_path = dir.to_path_buf();
+    ///
+    /// drop(dir);
+    /// assert!(file.exists());
+    ///
+    /// drop(file);
+    /// assert!(!dir_path.exists());
+    /// ```
+    #[track_caller]
+    pub fn child_file(&self) -> io::Result<Temp> {
+        self.child(Kind::File)
+    }
+
+    /// Create a temporary directory inside this directory which keeps it alive, see
+    /// [`child_file`](#method.child_file).
+    #[track_caller]
+    pub fn child_dir(&self) -> io::Result<Temp> {
+        self.child(Kind::Dir)
+    }
+
+    /// Create the directory `name` inside this directory, failing if it already exists, and
+    /// return a guard for it which keeps this directory alive, see
+    /// [`child_file`](#method.child_file). For building fixed layouts such as `bin/` and `lib/`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` isn't a single path component.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    ///
+    /// let staging = Temp::new_dir().unwrap().into_shared();
+    /// let bin = staging.reserve_dir("bin").unwrap();
+    /// let config = staging.reserve_file("config.toml").unwrap();
+    /// fs::write(&config, "debug = true").unwrap();
+    ///
+    /// assert!(bin.ends_with("bin"));
+    /// assert!(staging.reserve_dir("bin").is_err());
+    /// ```
+    #[track_caller]
+    pub fn reserve_dir(&self, name: &str) -> io::Result<Temp> {
+        self.reserve(name, Kind::Dir)
+    }
+
+    /// Create the empty file `name` inside this directory, see
+    /// [`reserve_dir`](#method.reserve_dir).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` isn't a single path component.
+    #[track_caller]
+    pub fn reserve_file(&self, name: &str) -> io::Result<Temp> {
+        self.reserve(name, Kind::File)
+    }
+
+    fn builder(&self) -> Builder {
+        let mut builder = Builder::new();
+        builder.in_dir(&**self);
+        if let Some(ref fs) = self.fs {
+            builder.fs(fs.clone());
+        }
+        builder
+    }
+
+    #[track_caller]
+    fn child(&self, kind: Kind) -> io::Result<Temp> {
+        let mut temp = self.builder().build(kind)?;
+        temp.parent = Some(self.clone());
+        Ok(temp)
+    }
+
+    #[track_caller]
+    fn reserve(&self, name: &str, kind: Kind) -> io::Result<Temp> {
+        let mut components = Path::new(name).components();
+        assert!(
+            matches!(
+                (components.next(), components.next()),
+                (Some(Component::Normal(_)), None)
+            ),
+            "{:?} is not a single path component",
+            name
+        );
+
+        let builder = self.builder();
+        let path = self.join(name);
+        limit::admit()?;
+        builder.create_at(&path, kind)?;
+
+        let mut temp = builder.wrap(path, kind);
+        temp.parent = Some(self.clone());
+        Ok(temp)
+    }
+}
+
+impl Temp {
+    /// The directory this temporary was created in with
+    /// [`SharedTemp::child_file`](struct.SharedTemp.html#method.child_file) or
+    /// [`child_dir`](struct.SharedTemp.html#method.child_dir), if it is still linked to it.
+    pub fn parent_temp(&self) -> Option<&SharedTemp> {
+        self.parent.as_ref()
+    }
+
+    /// Stop keeping the parent directory alive, e.g. after moving the temporary out of it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    ///
+    /// let staging = Temp::new_dir().unwrap().into_shared();
+    /// let output = Temp::new_dir().unwrap();
+    ///
+    /// let mut artifact = staging.child_file().unwrap();
+    /// artifact.move_into(&output).unwrap();
+    /// artifact.detach();
+    ///
+    /// let staging_path = staging.to_path_buf();
+    /// drop(staging);
+    /// assert!(!staging_path.exists());
+    /// ```
+    pub fn detach(&mut self) -> Option<SharedTemp> {
+        self.parent.take()
+    }
+
+    /// Keep `parent` alive until this temporary is dropped, replacing any previous link.
+    ///
+    /// Fails with `InvalidInput` if the temporary isn't inside `parent`.
+    pub fn adopt(&mut self, parent: SharedTemp) -> io::Result<()> {
+        if !self.path.starts_with(&*parent) || self.path == parent.path {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} is not inside {}",
+                    self.path.display(),
+                    parent.path.display()
+                ),
+            ));
+        }
+
+        self.parent = Some(parent);
+        Ok(())
+    }
+}
+
+impl From<Temp> for SharedTemp {
+    fn from(temp: Temp) -> Self {
+        SharedTemp::new(temp)
+    }
+}
+
+impl ops::Deref for SharedTemp {
+    type Target = Temp;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl AsRef<Path> for SharedTemp {
+    fn as_ref(&self) -> &Path {
+        self.inner.as_ref()
+    }
+}
+
+/// A weak handle to a [`SharedTemp`](struct.SharedTemp.html), which can observe the path but
+/// doesn't keep the temporary alive.
+#[derive(Debug, Clone)]
+pub struct WeakTemp {
+    path: PathBuf,
+    inner: Weak<Temp>,
+}
+
+impl WeakTemp {
+    /// The path of the temporary, which may no longer exist.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get a strong handle if the temporary is still alive.
+    pub fn upgrade(&self) -> Option<SharedTemp> {
+        self.inner.upgrade().map(|inner| SharedTemp { inner })
+    }
+
+    /// Whether any strong handle to the temporary is still alive.
+    pub fn is_alive(&self) -> bool {
+        self.inner.strong_count() > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_when_last_clone_drops() {
+        let shared = Temp::new_file().unwrap().into_shared();
+        let other = shared.clone();
+        let weak = shared.downgrade();
+
+        drop(shared);
+        assert!(weak.is_alive());
+        assert!(weak.path().exists());
+
+        drop(other);
+        assert!(!weak.is_alive());
+        assert!(!weak.path().exists());
+    }
+
+    #[test]
+    fn children_keep_parent_alive() {
+        let dir = Temp::new_dir().unwrap().into_shared();
+        let weak = dir.downgrade();
+        let child_dir = dir.child_dir().unwrap().into_shared();
+        let grandchild = child_dir.child_file().unwrap();
+        drop(dir);
+        drop(child_dir);
+
+        assert!(weak.is_alive());
+        assert!(grandchild.exists());
+        assert!(grandchild.starts_with(weak.path()));
+
+        drop(grandchild);
+        assert!(!weak.is_alive());
+        assert!(!weak.path().exists());
+    }
+
+    #[test]
+    fn adopt_links_and_detach_unlinks() {
+        let dir = Temp::new_dir().unwrap().into_shared();
+        let weak = dir.downgrade();
+        let mut file = Temp::new_file_in(&*dir).unwrap();
+
+        assert!(file.parent_temp().is_none());
+        file.adopt(dir).unwrap();
+        assert!(weak.is_alive());
+
+        assert!(file.detach().is_some());
+        assert!(!weak.is_alive());
+        assert!(file.parent_temp().is_none());
+    }
+
+    #[test]
+    fn adopt_rejects_unrelated_dirs() {
+        let dir = Temp::new_dir().unwrap().into_shared();
+        let mut file = Temp::new_file().unwrap();
+
+        match file.adopt(dir) {
+            Err(ref e) if e.kind() == io::ErrorKind::InvalidInput => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn upgrade_extends_lifetime() {
+        let shared = Temp::new_file().unwrap().into_shared();
+        let weak = shared.downgrade();
+
+        let upgraded = weak.upgrade().unwrap();
+        drop(shared);
+        assert!(upgraded.exists());
+    }
+
+    #[test]
+    fn reserved_names_are_exclusive() {
+        let dir = Temp::new_dir().unwrap().into_shared();
+        let data = dir.reserve_dir("data").unwrap();
+        assert!(data.is_dir());
+        assert_eq!(
+            data.parent_temp().map(|parent| parent.path()),
+            Some(dir.path())
+        );
+
+        match dir.reserve_file("data") {
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+            result => panic!("{:?}", result),
+        }
+
+        let path = data.to_path_buf();
+        drop(data);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    #[should_panic]
+    fn reserved_name_must_be_a_component() {
+        let dir = Temp::new_dir().unwrap().into_shared();
+        let _ = dir.reserve_dir("../escape");
+    }
+}
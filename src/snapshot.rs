@@ -0,0 +1,208 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use path_error::annotate;
+use typed::TempDir;
+
+/// The state of a directory tree, recorded by
+/// [`TempDir::snapshot`](struct.TempDir.html#method.snapshot) to compare with later.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeSnapshot {
+    // By path relative to the root.
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Entry {
+    Dir,
+    // The hash is of the content, or of the target for a symlink.
+    File { size: u64, hash: u64 },
+    Symlink { hash: u64 },
+}
+
+impl TreeSnapshot {
+    /// The paths in the tree, relative to its root, in order.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.entries.keys().map(|path| path.as_path())
+    }
+
+    /// The number of entries in the tree, not counting the root.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the tree was empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// What changed in a directory tree between two snapshots, returned by
+/// [`TempDir::diff_since`](struct.TempDir.html#method.diff_since). Paths are relative to the
+/// root of the tree and in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeDiff {
+    created: Vec<PathBuf>,
+    modified: Vec<PathBuf>,
+    deleted: Vec<PathBuf>,
+}
+
+impl TreeDiff {
+    /// The entries which didn't exist before, including those in a new directory.
+    pub fn created(&self) -> &[PathBuf] {
+        &self.created
+    }
+
+    /// The files and symlinks whose content or target changed, and the entries replaced by one
+    /// of another type.
+    pub fn modified(&self) -> &[PathBuf] {
+        &self.modified
+    }
+
+    /// The entries which no longer exist, including those in a deleted directory.
+    pub fn deleted(&self) -> &[PathBuf] {
+        &self.deleted
+    }
+
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.modified.is_empty() && self.deleted.is_empty()
+    }
+}
+
+impl TempDir {
+    /// Record the names, sizes and content hashes of everything in the directory, without
+    /// following symlinks, to find out later with [`diff_since`](#method.diff_since) what
+    /// changed. Modification times aren't compared, so rewriting a file with the same content
+    /// doesn't count as a change.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::TempDir;
+    /// use std::fs;
+    /// use std::path::PathBuf;
+    ///
+    /// let scratch = TempDir::new().unwrap();
+    /// fs::write(scratch.child("kept"), "1").unwrap();
+    /// fs::write(scratch.child("edited"), "1").unwrap();
+    /// let before = scratch.snapshot().unwrap();
+    ///
+    /// fs::write(scratch.child("edited"), "2").unwrap();
+    /// fs::write(scratch.child("new"), "").unwrap();
+    ///
+    /// let diff = scratch.diff_since(&before).unwrap();
+    /// assert_eq!(diff.created(), [PathBuf::from("new")]);
+    /// assert_eq!(diff.modified(), [PathBuf::from("edited")]);
+    /// assert!(diff.deleted().is_empty());
+    /// ```
+    pub fn snapshot(&self) -> io::Result<TreeSnapshot> {
+        let mut entries = BTreeMap::new();
+        record(self.path(), Path::new(""), &mut entries)?;
+        Ok(TreeSnapshot { entries })
+    }
+
+    /// Compare the directory with `snapshot`, taken earlier with [`snapshot`](#method.snapshot).
+    pub fn diff_since(&self, snapshot: &TreeSnapshot) -> io::Result<TreeDiff> {
+        let now = self.snapshot()?;
+        let mut diff = TreeDiff::default();
+        for (path, entry) in &now.entries {
+            match snapshot.entries.get(path) {
+                None => diff.created.push(path.clone()),
+                Some(before) if before != entry => diff.modified.push(path.clone()),
+                Some(_) => (),
+            }
+        }
+        diff.deleted = snapshot
+            .entries
+            .keys()
+            .filter(|path| !now.entries.contains_key(*path))
+            .cloned()
+            .collect();
+        Ok(diff)
+    }
+}
+
+fn record(root: &Path, dir: &Path, entries: &mut BTreeMap<PathBuf, Entry>) -> io::Result<()> {
+    let path = root.join(dir);
+    for dir_entry in annotate(fs::read_dir(&path), "read directory", &path)? {
+        let dir_entry = annotate(dir_entry, "read directory", &path)?;
+        let relative = dir.join(dir_entry.file_name());
+        let path = dir_entry.path();
+        let file_type = annotate(dir_entry.file_type(), "stat", &path)?;
+        let entry = if file_type.is_dir() {
+            record(root, &relative, entries)?;
+            Entry::Dir
+        } else if file_type.is_symlink() {
+            let target = annotate(fs::read_link(&path), "read link", &path)?;
+            let mut hasher = DefaultHasher::new();
+            hasher.write(target.to_string_lossy().as_bytes());
+            Entry::Symlink {
+                hash: hasher.finish(),
+            }
+        } else {
+            annotate(hash_file(&path), "read", &path)?
+        };
+        entries.insert(relative, entry);
+    }
+    Ok(())
+}
+
+fn hash_file(path: &Path) -> io::Result<Entry> {
+    let mut file = File::open(path)?;
+    let mut hasher = DefaultHasher::new();
+    let mut buf = [0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.write(&buf[..n]);
+        size += n as u64;
+    }
+    Ok(Entry::File {
+        size,
+        hash: hasher.finish(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_changes_are_reported() {
+        let scratch = TempDir::new().unwrap();
+        fs::create_dir_all(scratch.child("gone/deep")).unwrap();
+        fs::write(scratch.child("gone/deep/file"), "").unwrap();
+        fs::write(scratch.child("becomes_dir"), "").unwrap();
+        let before = scratch.snapshot().unwrap();
+        assert_eq!(before.len(), 4);
+        assert!(scratch.diff_since(&before).unwrap().is_empty());
+
+        fs::remove_dir_all(scratch.child("gone")).unwrap();
+        fs::remove_file(scratch.child("becomes_dir")).unwrap();
+        fs::create_dir(scratch.child("becomes_dir")).unwrap();
+        fs::write(scratch.child("becomes_dir/new"), "").unwrap();
+
+        let diff = scratch.diff_since(&before).unwrap();
+        assert_eq!(diff.created(), [Path::new("becomes_dir/new")]);
+        assert_eq!(diff.modified(), [Path::new("becomes_dir")]);
+        assert_eq!(
+            diff.deleted(),
+            [
+                Path::new("gone"),
+                Path::new("gone/deep"),
+                Path::new("gone/deep/file")
+            ]
+        );
+    }
+}
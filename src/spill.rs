@@ -0,0 +1,210 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use {Builder, Temp};
+
+enum Storage {
+    Memory(Vec<u8>),
+    File(Temp, File),
+}
+
+/// A byte buffer that is kept in memory up to a threshold, and moved into a temporary file when
+/// it grows beyond that, so large payloads don't exhaust memory while small ones never touch
+/// the disk.
+///
+/// Writes append to the end. Reads start at the beginning and continue where the last read
+/// left off.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::SpillBuffer;
+/// use std::io::{Read, Write};
+///
+/// let mut buffer = SpillBuffer::new(4);
+/// buffer.write_all(b"abc").unwrap();
+/// assert!(!buffer.is_spilled());
+///
+/// buffer.write_all(b"def").unwrap();
+/// assert!(buffer.is_spilled());
+///
+/// let mut contents = String::new();
+/// buffer.read_to_string(&mut contents).unwrap();
+/// assert_eq!(contents, "abcdef");
+/// ```
+pub struct SpillBuffer {
+    storage: Storage,
+    builder: Builder,
+    threshold: usize,
+    len: u64,
+    read: u64,
+}
+
+impl SpillBuffer {
+    /// Create an empty buffer that moves to a temporary file once it holds more than
+    /// `threshold` bytes.
+    pub fn new(threshold: usize) -> Self {
+        Self::with_builder(Builder::new(), threshold)
+    }
+
+    /// Create an empty buffer that creates its temporary file using `builder`.
+    pub fn with_builder(builder: Builder, threshold: usize) -> Self {
+        SpillBuffer {
+            storage: Storage::Memory(Vec::new()),
+            builder,
+            threshold,
+            len: 0,
+            read: 0,
+        }
+    }
+
+    /// The number of bytes written.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether nothing has been written.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the contents have moved into a temporary file.
+    pub fn is_spilled(&self) -> bool {
+        match self.storage {
+            Storage::Memory(_) => false,
+            Storage::File(..) => true,
+        }
+    }
+
+    /// All of the contents, read back from the file if they spilled.
+    pub fn into_bytes(self) -> io::Result<Vec<u8>> {
+        match self.storage {
+            Storage::Memory(bytes) => Ok(bytes),
+            Storage::File(temp, file) => {
+                drop(file);
+                fs::read(&temp)
+            }
+        }
+    }
+
+    /// The contents as a temporary file, writing them out if they haven't spilled yet, e.g. to
+    /// pass them to a program that takes a path.
+    pub fn into_temp(self) -> io::Result<Temp> {
+        match self.storage {
+            Storage::Memory(bytes) => {
+                let temp = self.builder.file()?;
+                fs::write(&temp, bytes)?;
+                Ok(temp)
+            }
+            Storage::File(temp, mut file) => {
+                file.flush()?;
+                Ok(temp)
+            }
+        }
+    }
+
+    fn spill(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let temp = self.builder.file()?;
+        let mut file = OpenOptions::new().read(true).write(true).open(&temp)?;
+        file.write_all(bytes)?;
+        self.storage = Storage::File(temp, file);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SpillBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SpillBuffer")
+            .field("spilled", &self.is_spilled())
+            .field("threshold", &self.threshold)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl Write for SpillBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let spill = match self.storage {
+            Storage::Memory(ref mut bytes) => {
+                if bytes.len() + buf.len() <= self.threshold {
+                    bytes.extend_from_slice(buf);
+                    self.len += buf.len() as u64;
+                    return Ok(buf.len());
+                }
+                ::std::mem::take(bytes)
+            }
+            Storage::File(_, ref mut file) => {
+                file.seek(SeekFrom::End(0))?;
+                let written = file.write(buf)?;
+                self.len += written as u64;
+                return Ok(written);
+            }
+        };
+
+        if let Err(e) = self.spill(&spill) {
+            // Keep what was buffered so far.
+            self.storage = Storage::Memory(spill);
+            return Err(e);
+        }
+        self.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.storage {
+            Storage::Memory(_) => Ok(()),
+            Storage::File(_, ref mut file) => file.flush(),
+        }
+    }
+}
+
+impl Read for SpillBuffer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = match self.storage {
+            Storage::Memory(ref bytes) => {
+                let mut rest = &bytes[self.read as usize..];
+                rest.read(buf)?
+            }
+            Storage::File(_, ref mut file) => {
+                file.seek(SeekFrom::Start(self.read))?;
+                file.read(buf)?
+            }
+        };
+        self.read += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_contents_stay_in_memory() {
+        let mut buffer = SpillBuffer::new(8);
+        buffer.write_all(b"12345678").unwrap();
+        assert!(!buffer.is_spilled());
+        assert_eq!(buffer.into_bytes().unwrap(), b"12345678");
+    }
+
+    #[test]
+    fn spilled_contents_are_kept_in_order() {
+        let mut buffer = SpillBuffer::new(2);
+        buffer.write_all(b"ab").unwrap();
+        let mut first = [0; 1];
+        buffer.read_exact(&mut first).unwrap();
+        buffer.write_all(b"cd").unwrap();
+        assert!(buffer.is_spilled());
+        assert_eq!(buffer.len(), 4);
+
+        let mut rest = Vec::new();
+        buffer.read_to_end(&mut rest).unwrap();
+        assert_eq!((&first[..], &rest[..]), (&b"a"[..], &b"bcd"[..]));
+
+        let temp = buffer.into_temp().unwrap();
+        assert_eq!(fs::read(&temp).unwrap(), b"abcd");
+    }
+}
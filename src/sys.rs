@@ -0,0 +1,794 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! Platform specific system calls.
+use std::fs;
+use std::io;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::os::unix::io::AsRawFd;
+#[cfg(windows)]
+use std::os::windows::ffi::OsStrExt;
+use std::path::{self, Path};
+use std::sync::Mutex;
+use std::time::SystemTime;
+#[cfg(any(unix, windows))]
+use std::time::UNIX_EPOCH;
+
+use path_error::{annotate, annotate2};
+
+#[cfg(unix)]
+pub fn cstr(path: &Path) -> io::Result<::std::ffi::CString> {
+    ::std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+#[allow(dead_code)]
+fn unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "operation not supported")
+}
+
+/// Whether an error means the operation isn't available on this platform or file system.
+pub fn is_unsupported(e: &io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        if let Some(code) = e.raw_os_error() {
+            return code == ::libc::ENOSYS || code == ::libc::EINVAL || code == ::libc::ENOTSUP;
+        }
+    }
+    #[cfg(windows)]
+    {
+        // ERROR_INVALID_FUNCTION, ERROR_NOT_SUPPORTED and ERROR_INVALID_PARAMETER.
+        if let Some(code) = e.raw_os_error() {
+            return code == 1 || code == 50 || code == 87;
+        }
+    }
+    e.kind() == io::ErrorKind::Unsupported
+}
+
+/// The name of this host, made safe to use in a file name, or `"localhost"` if it is unknown.
+/// Looked up once.
+pub fn hostname() -> String {
+    static HOSTNAME: Mutex<Option<String>> = Mutex::new(None);
+
+    let mut hostname = HOSTNAME.lock().unwrap_or_else(|e| e.into_inner());
+    hostname
+        .get_or_insert_with(|| {
+            let name = lookup_hostname().unwrap_or_default();
+            let name: String = name
+                .chars()
+                .map(|c| match c {
+                    c if path::is_separator(c) || c == ':' => '_',
+                    c => c,
+                })
+                .collect();
+            if name.is_empty() {
+                "localhost".to_owned()
+            } else {
+                name
+            }
+        })
+        .clone()
+}
+
+#[cfg(unix)]
+fn lookup_hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    if unsafe { ::libc::gethostname(buf.as_mut_ptr() as *mut _, buf.len()) } < 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Some(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
+
+#[cfg(windows)]
+fn lookup_hostname() -> Option<String> {
+    ::std::env::var("COMPUTERNAME").ok()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lookup_hostname() -> Option<String> {
+    None
+}
+
+/// The temp directory from `GetTempPath2W`, if this version of Windows has it.
+#[cfg(windows)]
+pub fn temp_path2() -> Option<::std::path::PathBuf> {
+    use std::ffi::{c_void, OsString};
+    use std::os::windows::ffi::OsStringExt;
+
+    type GetTempPath2W = unsafe extern "system" fn(len: u32, buf: *mut u16) -> u32;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetModuleHandleW(name: *const u16) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, name: *const u8) -> *mut c_void;
+    }
+
+    // Looked up at run time, since linking to it would stop older versions of Windows from
+    // loading the program at all.
+    let kernel32: Vec<u16> = "kernel32.dll".encode_utf16().chain(Some(0)).collect();
+    let function = unsafe {
+        let module = GetModuleHandleW(kernel32.as_ptr());
+        if module.is_null() {
+            return None;
+        }
+        GetProcAddress(module, b"GetTempPath2W\0".as_ptr())
+    };
+    if function.is_null() {
+        return None;
+    }
+    let function: GetTempPath2W = unsafe { ::std::mem::transmute(function) };
+
+    // The path is at most `MAX_PATH + 1` characters, including the trailing separator.
+    let mut buf = [0u16; 262];
+    let len = unsafe { function(buf.len() as u32, buf.as_mut_ptr()) } as usize;
+    if len == 0 || len >= buf.len() {
+        return None;
+    }
+    Some(OsString::from_wide(&buf[..len]).into())
+}
+
+/// The temp directory from `GetTempPath2W`, if this version of Windows has it.
+#[cfg(not(windows))]
+pub fn temp_path2() -> Option<::std::path::PathBuf> {
+    None
+}
+
+/// Let child processes inherit `file`, or stop them from doing so.
+#[cfg(unix)]
+pub fn set_inheritable(file: &fs::File, inheritable: bool) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    let flags = unsafe { ::libc::fcntl(fd, ::libc::F_GETFD) };
+    if flags < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let flags = if inheritable {
+        flags & !::libc::FD_CLOEXEC
+    } else {
+        flags | ::libc::FD_CLOEXEC
+    };
+    if unsafe { ::libc::fcntl(fd, ::libc::F_SETFD, flags) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Let child processes inherit `file`, or stop them from doing so.
+#[cfg(windows)]
+pub fn set_inheritable(file: &fs::File, inheritable: bool) -> io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+
+    const HANDLE_FLAG_INHERIT: u32 = 0x1;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetHandleInformation(handle: *mut ::std::ffi::c_void, mask: u32, flags: u32) -> i32;
+    }
+
+    let flags = if inheritable { HANDLE_FLAG_INHERIT } else { 0 };
+    if unsafe { SetHandleInformation(file.as_raw_handle() as *mut _, HANDLE_FLAG_INHERIT, flags) }
+        == 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Let child processes inherit `file`, or stop them from doing so.
+#[cfg(not(any(unix, windows)))]
+pub fn set_inheritable(_file: &fs::File, _inheritable: bool) -> io::Result<()> {
+    Err(unsupported())
+}
+
+/// Run `f` when the process exits normally, through the C runtime's `atexit`.
+#[cfg(unix)]
+pub fn at_exit(f: extern "C" fn()) -> io::Result<()> {
+    if unsafe { ::libc::atexit(f) } != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "failed to register an exit handler",
+        ));
+    }
+    Ok(())
+}
+
+/// Make `permissions` read-only, or writable again by the owner only. On unix
+/// `Permissions::set_readonly(false)` sets every write bit, making the file world-writable.
+pub fn set_readonly(permissions: &mut fs::Permissions, readonly: bool) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = permissions.mode();
+        permissions.set_mode(if readonly {
+            mode & !0o222
+        } else {
+            mode | 0o200
+        });
+    }
+    #[cfg(not(unix))]
+    permissions.set_readonly(readonly);
+}
+
+/// Run `f` when the process exits normally, through the C runtime's `atexit`.
+#[cfg(windows)]
+pub fn at_exit(f: extern "C" fn()) -> io::Result<()> {
+    extern "C" {
+        fn atexit(f: extern "C" fn()) -> i32;
+    }
+
+    if unsafe { atexit(f) } != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "failed to register an exit handler",
+        ));
+    }
+    Ok(())
+}
+
+/// Run `f` when the process exits normally, through the C runtime's `atexit`.
+#[cfg(not(any(unix, windows)))]
+pub fn at_exit(_f: extern "C" fn()) -> io::Result<()> {
+    Err(unsupported())
+}
+
+/// An exclusive lock taken with [`lock`](fn.lock.html), released when it is dropped.
+#[derive(Debug)]
+pub struct Lock {
+    #[cfg(unix)]
+    path: ::std::path::PathBuf,
+    _file: fs::File,
+}
+
+/// Take the exclusive lock `.{name}.lock` in `dir`, blocking until it is free. It is released
+/// when the `Lock` is dropped, or the process dies, and the lock file is removed by the last
+/// holder.
+#[cfg(unix)]
+pub fn lock(dir: &Path, name: &str) -> io::Result<Lock> {
+    use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+    use std::os::unix::io::AsRawFd;
+
+    let path = dir.join(format!(".{}.lock", name));
+    loop {
+        let file = annotate(
+            fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .mode(0o600)
+                .custom_flags(::libc::O_NOFOLLOW)
+                .open(&path),
+            "open",
+            &path,
+        )?;
+        while unsafe { ::libc::flock(file.as_raw_fd(), ::libc::LOCK_EX) } != 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() != io::ErrorKind::Interrupted {
+                return annotate(Err(e), "lock", &path);
+            }
+        }
+
+        // The previous holder removes the file when it is done, so the lock only counts if the
+        // file is still the one at `path`.
+        let locked = file.metadata()?;
+        match fs::symlink_metadata(&path) {
+            Ok(ref current) if current.dev() == locked.dev() && current.ino() == locked.ino() => {
+                return Ok(Lock { path, _file: file })
+            }
+            _ => continue,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // Still holding the lock, so waiters on this file find it gone and open a new one.
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Take the exclusive lock `.{name}.lock` in `dir`, blocking until it is free. It is released
+/// when the `Lock` is dropped, or the process dies, and the lock file is removed by the last
+/// holder.
+#[cfg(windows)]
+pub fn lock(dir: &Path, name: &str) -> io::Result<Lock> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::thread;
+    use std::time::Duration;
+
+    const GENERIC_READ: u32 = 0x8000_0000;
+    const GENERIC_WRITE: u32 = 0x4000_0000;
+    const DELETE: u32 = 0x0001_0000;
+    const FILE_FLAG_DELETE_ON_CLOSE: u32 = 0x0400_0000;
+    // ERROR_ACCESS_DENIED, while the last holder's file is being deleted, and
+    // ERROR_SHARING_VIOLATION
+    const BUSY: [i32; 2] = [5, 32];
+
+    // Nobody else may open the file while it is held, and it is gone once it is closed.
+    let path = dir.join(format!(".{}.lock", name));
+    loop {
+        let result = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .access_mode(GENERIC_READ | GENERIC_WRITE | DELETE)
+            .share_mode(0)
+            .custom_flags(FILE_FLAG_DELETE_ON_CLOSE)
+            .open(&path);
+        match result {
+            Err(ref e) if e.raw_os_error().map_or(false, |code| BUSY.contains(&code)) => {
+                thread::sleep(Duration::from_millis(1))
+            }
+            result => return annotate(result, "lock", &path).map(|file| Lock { _file: file }),
+        }
+    }
+}
+
+/// Take the exclusive lock `.{name}.lock` in `dir`, blocking until it is free.
+#[cfg(not(any(unix, windows)))]
+pub fn lock(_dir: &Path, _name: &str) -> io::Result<Lock> {
+    Err(unsupported())
+}
+
+/// Atomically exchange `a` and `b`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn rename_exchange(a: &Path, b: &Path) -> io::Result<()> {
+    let (a, b) = (cstr(a)?, cstr(b)?);
+    let result = unsafe {
+        ::libc::syscall(
+            ::libc::SYS_renameat2,
+            ::libc::AT_FDCWD,
+            a.as_ptr(),
+            ::libc::AT_FDCWD,
+            b.as_ptr(),
+            ::libc::RENAME_EXCHANGE,
+        )
+    };
+
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Atomically exchange `a` and `b`.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn rename_exchange(_a: &Path, _b: &Path) -> io::Result<()> {
+    Err(unsupported())
+}
+
+/// Make `to`, a new empty file, share the data of `from`, on file systems with copy-on-write
+/// support.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn clone_into(from: &fs::File, to: &fs::File) -> io::Result<()> {
+    // _IOW(0x94, 9, int)
+    const FICLONE: u32 = 0x4004_9409;
+
+    if unsafe { ::libc::ioctl(to.as_raw_fd(), FICLONE as _, from.as_raw_fd()) } < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Make `to`, a new empty file, share the data of `from`, on file systems with copy-on-write
+/// support.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn clone_into(_from: &fs::File, _to: &fs::File) -> io::Result<()> {
+    Err(unsupported())
+}
+
+/// Create `to` sharing the data of `from`, which fails without creating anything if it can't.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn clonefile(from: &Path, to: &Path) -> io::Result<()> {
+    extern "C" {
+        fn clonefile(src: *const ::libc::c_char, dst: *const ::libc::c_char, flags: u32) -> i32;
+    }
+
+    let (from, to) = (cstr(from)?, cstr(to)?);
+    if unsafe { clonefile(from.as_ptr(), to.as_ptr(), 0) } < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Copy a file to `to`, which must not exist, cloning its data where possible.
+///
+/// `to` is only ever created exclusively and written through that handle, so a symlink
+/// planted at `to` in a shared directory is never followed.
+pub fn copy_file(from: &Path, to: &Path) -> io::Result<()> {
+    let exists = || {
+        io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to.display()),
+        )
+    };
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    match clonefile(from, to) {
+        Ok(()) => return Ok(()),
+        Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => return Err(exists()),
+        Err(_) => (),
+    }
+
+    let mut src = annotate(fs::File::open(from), "open", from)?;
+    let mut dst = match fs::OpenOptions::new().write(true).create_new(true).open(to) {
+        Ok(dst) => dst,
+        Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => return Err(exists()),
+        Err(e) => return annotate(Err(e), "create", to),
+    };
+
+    if clone_into(&src, &dst).is_err() {
+        annotate2(io::copy(&mut src, &mut dst), "copy", from, to)?;
+    }
+    dst.set_permissions(src.metadata()?.permissions())
+}
+
+/// Have Windows delete `path`, and everything in it, the next time it starts.
+#[cfg(windows)]
+pub fn delete_on_reboot(path: &Path) -> io::Result<()> {
+    const MOVEFILE_DELAY_UNTIL_REBOOT: u32 = 0x4;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn MoveFileExW(existing: *const u16, new: *const u16, flags: u32) -> i32;
+    }
+
+    // Directories are only deleted once empty, so their entries have to be scheduled first.
+    if fs::symlink_metadata(path)?.is_dir() {
+        for entry in fs::read_dir(path)? {
+            delete_on_reboot(&entry?.path())?;
+        }
+    }
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    if unsafe {
+        MoveFileExW(
+            wide.as_ptr(),
+            ::std::ptr::null(),
+            MOVEFILE_DELAY_UNTIL_REBOOT,
+        )
+    } == 0
+    {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Delete the file, symlink or empty directory at `path` with POSIX semantics, so its name is
+/// released as soon as this returns rather than once the last open handle to it is closed.
+///
+/// Fails with an unsupported error before Windows 10 1607 and on file systems without support,
+/// such as FAT.
+#[cfg(windows)]
+pub fn posix_delete(path: &Path) -> io::Result<()> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+
+    const DELETE: u32 = 0x0001_0000;
+    const FILE_SHARE_ALL: u32 = 0x1 | 0x2 | 0x4;
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+    const FILE_FLAG_OPEN_REPARSE_POINT: u32 = 0x0020_0000;
+    const FILE_DISPOSITION_INFO_EX: i32 = 21;
+    const FILE_DISPOSITION_FLAG_DELETE: u32 = 0x1;
+    const FILE_DISPOSITION_FLAG_POSIX_SEMANTICS: u32 = 0x2;
+    const FILE_DISPOSITION_FLAG_IGNORE_READONLY_ATTRIBUTE: u32 = 0x10;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetFileInformationByHandle(
+            handle: *mut ::std::ffi::c_void,
+            class: i32,
+            info: *const ::std::ffi::c_void,
+            size: u32,
+        ) -> i32;
+    }
+
+    // Opening the reparse point itself means a symlink is deleted rather than its target.
+    let file = fs::OpenOptions::new()
+        .access_mode(DELETE)
+        .share_mode(FILE_SHARE_ALL)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT)
+        .open(path)?;
+
+    let flags: u32 = FILE_DISPOSITION_FLAG_DELETE
+        | FILE_DISPOSITION_FLAG_POSIX_SEMANTICS
+        | FILE_DISPOSITION_FLAG_IGNORE_READONLY_ATTRIBUTE;
+    if unsafe {
+        SetFileInformationByHandle(
+            file.as_raw_handle() as *mut _,
+            FILE_DISPOSITION_INFO_EX,
+            &flags as *const u32 as *const _,
+            ::std::mem::size_of::<u32>() as u32,
+        )
+    } == 0
+    {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Remove a directory tree, deleting each entry with [`posix_delete`] so the directory is
+/// really empty by the time it is removed itself.
+#[cfg(windows)]
+pub fn posix_remove_dir_all(path: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        // Directory symlinks and junctions report as symlinks, so they aren't followed.
+        if entry.file_type()?.is_dir() {
+            posix_remove_dir_all(&entry.path())?;
+        } else {
+            posix_delete(&entry.path())?;
+        }
+    }
+    posix_delete(path)
+}
+
+/// Have the system delete `path` the next time it starts.
+#[cfg(not(windows))]
+pub fn delete_on_reboot(_path: &Path) -> io::Result<()> {
+    Err(unsupported())
+}
+
+/// Keep Windows Search from indexing `path`, by setting `FILE_ATTRIBUTE_NOT_CONTENT_INDEXED`.
+#[cfg(windows)]
+pub fn set_not_content_indexed(path: &Path) -> io::Result<()> {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_NOT_CONTENT_INDEXED: u32 = 0x2000;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetFileAttributesW(path: *const u16, attributes: u32) -> i32;
+    }
+
+    let attributes = fs::symlink_metadata(path)?.file_attributes();
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    if unsafe {
+        SetFileAttributesW(
+            wide.as_ptr(),
+            attributes | FILE_ATTRIBUTE_NOT_CONTENT_INDEXED,
+        )
+    } == 0
+    {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Keep Windows Search from indexing `path`; there is nothing to do elsewhere.
+#[cfg(not(windows))]
+pub fn set_not_content_indexed(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Set the modification time of `path`, leaving its access time alone.
+#[cfg(unix)]
+pub fn set_modified(path: &Path, time: SystemTime) -> io::Result<()> {
+    let (secs, nanos) = match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => (since.as_secs() as i64, since.subsec_nanos() as i64),
+        Err(e) => {
+            let before = e.duration();
+            match before.subsec_nanos() {
+                0 => (-(before.as_secs() as i64), 0),
+                nanos => (-(before.as_secs() as i64) - 1, 1_000_000_000 - nanos as i64),
+            }
+        }
+    };
+
+    let path = cstr(path)?;
+    let mut times = unsafe { ::std::mem::zeroed::<[::libc::timespec; 2]>() };
+    times[0].tv_nsec = ::libc::UTIME_OMIT;
+    times[1].tv_sec = secs as ::libc::time_t;
+    times[1].tv_nsec = nanos as _;
+    if unsafe { ::libc::utimensat(::libc::AT_FDCWD, path.as_ptr(), times.as_ptr(), 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Set the modification time of `path`, leaving its access time alone.
+#[cfg(windows)]
+pub fn set_modified(path: &Path, time: SystemTime) -> io::Result<()> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+
+    const FILE_WRITE_ATTRIBUTES: u32 = 0x100;
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+    // Seconds from 1601, where Windows file times start, to 1970.
+    const EPOCH_OFFSET: u64 = 11_644_473_600;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetFileTime(
+            handle: *mut ::std::ffi::c_void,
+            created: *const u64,
+            accessed: *const u64,
+            written: *const u64,
+        ) -> i32;
+    }
+
+    let since = time
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let written = (since.as_secs() + EPOCH_OFFSET) * 10_000_000 + since.subsec_nanos() as u64 / 100;
+
+    // Directories can only be opened with backup semantics.
+    let file = fs::OpenOptions::new()
+        .access_mode(FILE_WRITE_ATTRIBUTES)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)?;
+    if unsafe {
+        SetFileTime(
+            file.as_raw_handle() as *mut _,
+            ::std::ptr::null(),
+            ::std::ptr::null(),
+            &written,
+        )
+    } == 0
+    {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Set the modification time of `path`, leaving its access time alone.
+#[cfg(not(any(unix, windows)))]
+pub fn set_modified(_path: &Path, _time: SystemTime) -> io::Result<()> {
+    Err(unsupported())
+}
+
+/// Whether `dir` is on a file system mounted `noexec`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn is_noexec(dir: &Path) -> io::Result<bool> {
+    let dir = cstr(dir)?;
+    let mut stat = unsafe { ::std::mem::zeroed::<::libc::statvfs>() };
+    if unsafe { ::libc::statvfs(dir.as_ptr(), &mut stat) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stat.f_flag & ::libc::ST_NOEXEC != 0)
+}
+
+/// Whether `dir` is on a file system mounted `noexec`.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub fn is_noexec(dir: &Path) -> io::Result<bool> {
+    let dir = cstr(dir)?;
+    let mut stat = unsafe { ::std::mem::zeroed::<::libc::statfs>() };
+    if unsafe { ::libc::statfs(dir.as_ptr(), &mut stat) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stat.f_flags as i64 & ::libc::MNT_NOEXEC as i64 != 0)
+}
+
+/// Whether `dir` is on a file system mounted `noexec`.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios"
+)))]
+pub fn is_noexec(_dir: &Path) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// Remove a directory tree relative to open directory handles (`openat`/`unlinkat`) rather
+/// than by path, so each path is only resolved once and a directory swapped for a symlink
+/// while the tree is being removed is never followed.
+#[cfg(all(unix, feature = "fast_remove"))]
+pub fn remove_dir_all(path: &Path) -> io::Result<()> {
+    let path = cstr(path)?;
+    let fd = unsafe {
+        ::libc::open(
+            path.as_ptr(),
+            ::libc::O_RDONLY | ::libc::O_DIRECTORY | ::libc::O_NOFOLLOW | ::libc::O_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let result = remove_dir_contents(fd);
+    unsafe { ::libc::close(fd) };
+    result?;
+    if unsafe { ::libc::rmdir(path.as_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(all(unix, feature = "fast_remove"))]
+fn remove_dir_contents(fd: ::std::os::unix::io::RawFd) -> io::Result<()> {
+    use std::ffi::CStr;
+
+    // Read all the names first, the order entries are returned in is unspecified once the
+    // directory is modified.
+    let mut names = Vec::new();
+    unsafe {
+        let dup = ::libc::fcntl(fd, ::libc::F_DUPFD_CLOEXEC, 0);
+        if dup < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let dir = ::libc::fdopendir(dup);
+        if dir.is_null() {
+            let e = io::Error::last_os_error();
+            ::libc::close(dup);
+            return Err(e);
+        }
+        loop {
+            let entry = ::libc::readdir(dir);
+            if entry.is_null() {
+                break;
+            }
+            let name = CStr::from_ptr((*entry).d_name.as_ptr());
+            if name.to_bytes() != b"." && name.to_bytes() != b".." {
+                names.push(name.to_owned());
+            }
+        }
+        ::libc::closedir(dir);
+    }
+
+    for name in names {
+        if unsafe { ::libc::unlinkat(fd, name.as_ptr(), 0) } == 0 {
+            continue;
+        }
+        let e = io::Error::last_os_error();
+        match e.raw_os_error() {
+            // Linux reports EISDIR for directories, POSIX allows EPERM.
+            Some(::libc::EISDIR) | Some(::libc::EPERM) => {}
+            Some(::libc::ENOENT) => continue,
+            _ => return Err(e),
+        }
+
+        let child = unsafe {
+            ::libc::openat(
+                fd,
+                name.as_ptr(),
+                ::libc::O_RDONLY | ::libc::O_DIRECTORY | ::libc::O_NOFOLLOW | ::libc::O_CLOEXEC,
+            )
+        };
+        if child < 0 {
+            // Not a directory after all, so the unlink failed for another reason.
+            return Err(e);
+        }
+        let result = remove_dir_contents(child);
+        unsafe { ::libc::close(child) };
+        result?;
+
+        if unsafe { ::libc::unlinkat(fd, name.as_ptr(), ::libc::AT_REMOVEDIR) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix, feature = "fast_remove"))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use Temp;
+
+    #[test]
+    fn removes_tree_without_following_symlinks() {
+        let outside = Temp::new_dir().unwrap();
+        fs::write(outside.join("keep"), b"data").unwrap();
+
+        let dir = Temp::new_dir().unwrap();
+        fs::create_dir_all(dir.join("a").join("b")).unwrap();
+        fs::write(dir.join("a").join("b").join("file"), b"data").unwrap();
+        fs::write(dir.join("file"), b"data").unwrap();
+        symlink(&outside, dir.join("a").join("link")).unwrap();
+
+        remove_dir_all(&dir).unwrap();
+        assert!(!dir.exists());
+        assert!(outside.join("keep").exists());
+    }
+}
@@ -0,0 +1,107 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use {Builder, Temp};
+
+/// A line oriented log kept in a temporary file. Writes are buffered and flushed when the log
+/// is read, converted back into a [`Temp`](struct.Temp.html), or dropped.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::TempLog;
+///
+/// let mut log = TempLog::new().unwrap();
+/// log.append_line("started").unwrap();
+/// log.append_line("finished").unwrap();
+/// assert_eq!(log.read_to_string().unwrap(), "started\nfinished\n");
+/// ```
+#[derive(Debug)]
+pub struct TempLog {
+    writer: BufWriter<File>,
+    temp: Option<Temp>,
+}
+
+impl TempLog {
+    /// Create an empty log in the system temp directory.
+    pub fn new() -> io::Result<Self> {
+        Self::with_builder(&Builder::new())
+    }
+
+    /// Create an empty log using `builder`.
+    pub fn with_builder(builder: &Builder) -> io::Result<Self> {
+        let temp = builder.file()?;
+        let file = OpenOptions::new().append(true).open(&temp)?;
+
+        Ok(TempLog {
+            writer: BufWriter::new(file),
+            temp: Some(temp),
+        })
+    }
+
+    /// Append `line` followed by a newline.
+    pub fn append_line(&mut self, line: &str) -> io::Result<()> {
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.write_all(b"\n")
+    }
+
+    /// Write any buffered lines to the file.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// The path of the log file.
+    pub fn path(&self) -> &Path {
+        self.temp().as_ref()
+    }
+
+    /// Flush and read back the whole log.
+    pub fn read_to_string(&mut self) -> io::Result<String> {
+        self.flush()?;
+        fs::read_to_string(self.path())
+    }
+
+    /// Flush and hand back the log file, e.g. to keep or upload it.
+    pub fn into_temp(mut self) -> io::Result<Temp> {
+        self.flush()?;
+        Ok(self.temp.take().expect("temp is only taken here"))
+    }
+
+    fn temp(&self) -> &Temp {
+        self.temp.as_ref().expect("temp is only taken by into_temp")
+    }
+}
+
+impl Drop for TempLog {
+    fn drop(&mut self) {
+        let _result = self.writer.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_flushed_into_temp() {
+        let mut log = TempLog::new().unwrap();
+        log.append_line("one").unwrap();
+
+        let temp = log.into_temp().unwrap();
+        assert_eq!(fs::read_to_string(&temp).unwrap(), "one\n");
+    }
+
+    #[test]
+    fn log_is_removed_on_drop() {
+        let mut log = TempLog::new().unwrap();
+        log.append_line("one").unwrap();
+
+        let path = log.path().to_path_buf();
+        drop(log);
+        assert!(!path.exists());
+    }
+}
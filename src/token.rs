@@ -0,0 +1,165 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use uuid::Uuid;
+use {Builder, Kind, Temp};
+
+/// The right to clean up a temporary, handed to another process, or across any other boundary
+/// that only carries strings, with [`Temp::into_token`](struct.Temp.html#method.into_token).
+///
+/// A token is redeemed with [`Temp::from_token`](struct.Temp.html#method.from_token), which
+/// succeeds only once, so exactly one guard owns the temporary at a time. Its string form,
+/// through `Display` and `FromStr`, is `kind:nonce:path`; paths that aren't valid Unicode don't
+/// survive it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TempToken {
+    path: PathBuf,
+    kind: Kind,
+    nonce: String,
+}
+
+impl TempToken {
+    /// The path of the temporary.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The marker whose removal claims the token, next to the temporary.
+    fn marker(&self) -> PathBuf {
+        let mut name = OsString::from(".");
+        name.push(self.path.file_name().unwrap_or_default());
+        name.push(format!(".{}.token", self.nonce));
+        self.path.with_file_name(name)
+    }
+}
+
+impl fmt::Display for TempToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            Kind::File => 'f',
+            Kind::Dir => 'd',
+            Kind::Path => 'p',
+        };
+        write!(f, "{}:{}:{}", kind, self.nonce, self.path.display())
+    }
+}
+
+impl FromStr for TempToken {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid temporary token");
+
+        let mut parts = s.splitn(3, ':');
+        let kind = match parts.next() {
+            Some("f") => Kind::File,
+            Some("d") => Kind::Dir,
+            Some("p") => Kind::Path,
+            _ => return Err(invalid()),
+        };
+        let nonce = parts.next().ok_or_else(invalid)?;
+        if nonce.is_empty() || !nonce.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let path = parts
+            .next()
+            .filter(|path| !path.is_empty())
+            .ok_or_else(invalid)?;
+
+        Ok(TempToken {
+            path: PathBuf::from(path),
+            kind,
+            nonce: nonce.to_owned(),
+        })
+    }
+}
+
+impl Temp {
+    /// Give up the guard in exchange for a token another process can redeem with
+    /// [`from_token`](#method.from_token) to take over cleanup. A marker file is created next to
+    /// the temporary until the token is redeemed. If that fails, the temporary is removed as if
+    /// dropped.
+    ///
+    /// The receiving guard uses the default settings, such as the
+    /// [`OsFs`](backend/struct.OsFs.html) backend, rather than this guard's
+    /// [`Builder`](struct.Builder.html) settings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::{Temp, TempToken};
+    /// use std::fs;
+    ///
+    /// let temp_dir = Temp::new_dir().unwrap();
+    /// // e.g. passed to a child process on its command line
+    /// let token = temp_dir.into_token().unwrap().to_string();
+    ///
+    /// let token: TempToken = token.parse().unwrap();
+    /// let temp_dir = Temp::from_token(&token).unwrap();
+    /// assert!(Temp::from_token(&token).is_err());
+    /// ```
+    pub fn into_token(self) -> io::Result<TempToken> {
+        let token = TempToken {
+            path: self.path.clone(),
+            kind: self.kind,
+            nonce: Uuid::new_v4().simple().to_string(),
+        };
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(token.marker())?;
+
+        self.defuse();
+        Ok(token)
+    }
+
+    /// Take over cleanup of the temporary behind `token`, see
+    /// [`into_token`](#method.into_token). Fails with `NotFound` if the token was already
+    /// redeemed.
+    #[track_caller]
+    pub fn from_token(token: &TempToken) -> io::Result<Self> {
+        // Removing the marker is atomic, so only one caller can succeed.
+        match fs::remove_file(token.marker()) {
+            Ok(()) => Ok(Builder::new().wrap(token.path.clone(), token.kind)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "the token for {} was already redeemed",
+                    token.path.display()
+                ),
+            )),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_round_trips_through_string() {
+        let temp = Temp::new_file().unwrap();
+        let path = temp.to_path_buf();
+        let token = temp.into_token().unwrap();
+        assert!(path.exists());
+
+        let parsed: TempToken = token.to_string().parse().unwrap();
+        assert_eq!(parsed, token);
+        assert!("x:00:/tmp/a".parse::<TempToken>().is_err());
+        assert!("f:zz:/tmp/a".parse::<TempToken>().is_err());
+
+        let temp = Temp::from_token(&parsed).unwrap();
+        assert_eq!(temp.kind(), Kind::File);
+        assert!(!token.marker().exists());
+        drop(temp);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,89 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use registry::Site;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TOMBSTONES: Mutex<Option<HashMap<PathBuf, Site>>> = Mutex::new(None);
+
+/// Turn on or off recording the temporaries that guards remove, in debug builds, so that
+/// creating a temporary inside one of them later, through a copy of its path kept after the
+/// guard was dropped, panics instead of failing with a confusing `NotFound` or silently
+/// recreating the directory. Has no effect in release builds.
+///
+/// Every removed path is kept until this is turned off, so it is meant for test suites.
+///
+/// # Examples
+///
+/// ```should_panic
+/// use mktemp::Temp;
+///
+/// mktemp::set_tombstones(true);
+/// let path = {
+///     let scratch = Temp::new_dir().unwrap();
+///     scratch.to_path_buf()
+/// };
+/// // Panics: the directory was removed with its guard.
+/// let _temp_file = Temp::new_file_in(&path);
+/// ```
+pub fn set_tombstones(enabled: bool) {
+    if cfg!(debug_assertions) {
+        let mut tombstones = TOMBSTONES.lock().unwrap_or_else(|e| e.into_inner());
+        ENABLED.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            *tombstones = None;
+        }
+    }
+}
+
+fn enabled() -> bool {
+    cfg!(debug_assertions) && ENABLED.load(Ordering::Relaxed)
+}
+
+/// Record that the temporary at `path`, created at `site`, was removed.
+pub(crate) fn bury(path: &Path, site: Site) {
+    if enabled() {
+        TOMBSTONES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert_with(HashMap::new)
+            .insert(path.to_path_buf(), site);
+    }
+}
+
+/// Panic if `path` is, or is inside, a temporary that was removed.
+pub(crate) fn check(path: &Path) {
+    if !enabled() {
+        return;
+    }
+    let found = {
+        let tombstones = TOMBSTONES.lock().unwrap_or_else(|e| e.into_inner());
+        let tombstones = match *tombstones {
+            Some(ref tombstones) => tombstones,
+            None => return,
+        };
+        path.ancestors()
+            .find_map(|ancestor| tombstones.get_key_value(ancestor))
+            .map(|(removed, site)| (removed.clone(), *site))
+    };
+    if let Some((removed, site)) = found {
+        match site {
+            Some(site) => panic!(
+                "{} is inside {}, a temporary created at {} that was already removed",
+                path.display(),
+                removed.display(),
+                site
+            ),
+            None => panic!(
+                "{} is inside {}, a temporary that was already removed",
+                path.display(),
+                removed.display()
+            ),
+        }
+    }
+}
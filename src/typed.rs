@@ -0,0 +1,286 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::convert::TryFrom;
+use std::ffi::OsStr;
+use std::fs::{self, ReadDir};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use path_error::annotate;
+use {entry_names, Builder, Kind, Temp};
+
+/// A temporary directory, for APIs that need one to be a directory at compile time, like
+/// [`TempFile`](struct.TempFile.html) for files. It is created with
+/// [`Builder::temp_dir`](struct.Builder.html#method.temp_dir) or converted from a
+/// [`Temp`](struct.Temp.html) of kind `Dir` with `TryFrom`.
+///
+/// Methods that only make sense for a directory, such as [`child`](#method.child),
+/// [`is_empty`](#method.is_empty) and [`snapshot`](#method.snapshot), are only here and not on
+/// `Temp`. Children that keep a shared directory alive are created through
+/// [`SharedTemp`](struct.SharedTemp.html), which can hold a temporary of any kind.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::TempDir;
+/// use std::fs;
+///
+/// fn write_report(out: &TempDir) {
+///     fs::write(out.child("report.txt"), "ok").unwrap();
+/// }
+///
+/// let out = TempDir::new().unwrap();
+/// write_report(&out);
+/// assert_eq!(out.entries().unwrap().count(), 1);
+/// ```
+#[derive(Debug)]
+pub struct TempDir {
+    temp: Temp,
+}
+
+impl TempDir {
+    /// Create a temporary directory in the system temp directory.
+    #[track_caller]
+    pub fn new() -> io::Result<Self> {
+        Builder::new().temp_dir()
+    }
+
+    /// Create a temporary directory in `dir`.
+    #[track_caller]
+    pub fn new_in<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        Builder::new().in_dir(dir).temp_dir()
+    }
+
+    /// The path of the directory.
+    pub fn path(&self) -> &Path {
+        self.temp.path()
+    }
+
+    /// The path of the entry `name` in the directory, which need not exist.
+    pub fn child<S: AsRef<OsStr>>(&self, name: S) -> PathBuf {
+        self.temp.join(name.as_ref())
+    }
+
+    /// The entries of the directory.
+    pub fn entries(&self) -> io::Result<ReadDir> {
+        annotate(fs::read_dir(self.path()), "read directory", self.path())
+    }
+
+    /// Whether the directory has no entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::TempDir;
+    /// use std::fs;
+    ///
+    /// let temp_dir = TempDir::new().unwrap();
+    /// assert!(temp_dir.is_empty().unwrap());
+    /// fs::write(temp_dir.child("file"), "").unwrap();
+    /// assert!(!temp_dir.is_empty().unwrap());
+    /// ```
+    pub fn is_empty(&self) -> io::Result<bool> {
+        Ok(self.entries()?.next().is_none())
+    }
+
+    /// Panic, listing the entries, unless the directory is empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory has entries or can't be read.
+    #[track_caller]
+    pub fn assert_empty(&self) {
+        let entries = match entry_names(self.path()) {
+            Ok(entries) => entries,
+            Err(e) => panic!("failed to read {}: {}", self.temp.describe(), e),
+        };
+        assert!(
+            entries.is_empty(),
+            "{} is not empty, it contains {:?}",
+            self.temp.describe(),
+            entries
+        );
+    }
+
+    /// The guard, to use the methods of [`Temp`](struct.Temp.html).
+    pub fn as_temp(&self) -> &Temp {
+        &self.temp
+    }
+
+    /// Turn back into an untyped guard.
+    pub fn into_temp(self) -> Temp {
+        self.temp
+    }
+
+    /// Give up ownership, see [`Temp::release`](struct.Temp.html#method.release).
+    pub fn release(self) -> PathBuf {
+        self.temp.release()
+    }
+
+    /// Remove the directory now, see [`Temp::close`](struct.Temp.html#method.close).
+    pub fn close(self) -> io::Result<()> {
+        self.temp.close()
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl TryFrom<Temp> for TempDir {
+    type Error = Temp;
+
+    /// Fails, giving the guard back, unless it was created as a directory.
+    fn try_from(temp: Temp) -> Result<Self, Temp> {
+        match temp.kind {
+            Kind::Dir => Ok(TempDir { temp }),
+            _ => Err(temp),
+        }
+    }
+}
+
+impl From<TempDir> for Temp {
+    fn from(dir: TempDir) -> Self {
+        dir.temp
+    }
+}
+
+/// A path for something to create, that is removed, whatever it turns out to be, when the
+/// guard is dropped. It is created with
+/// [`Builder::temp_path`](struct.Builder.html#method.temp_path) or converted from a
+/// [`Temp`](struct.Temp.html) of kind `Path` with `TryFrom`.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::TempPath;
+/// use std::fs;
+///
+/// let socket = TempPath::new();
+/// assert!(!socket.path().exists());
+/// fs::write(&socket, "").unwrap();
+/// ```
+#[derive(Debug)]
+pub struct TempPath {
+    temp: Temp,
+}
+
+impl TempPath {
+    /// A path in the system temp directory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the default directory is rejected because of
+    /// [`set_symlink_free_root`](fn.set_symlink_free_root.html), see
+    /// [`Builder::try_path`](struct.Builder.html#method.try_path).
+    #[track_caller]
+    pub fn new() -> Self {
+        Builder::new().temp_path()
+    }
+
+    /// A path in `dir`.
+    #[track_caller]
+    pub fn new_in<P: AsRef<Path>>(dir: P) -> Self {
+        Builder::new().in_dir(dir).temp_path()
+    }
+
+    /// The path.
+    pub fn path(&self) -> &Path {
+        self.temp.path()
+    }
+
+    /// The guard, to use the methods of [`Temp`](struct.Temp.html).
+    pub fn as_temp(&self) -> &Temp {
+        &self.temp
+    }
+
+    /// Turn back into an untyped guard.
+    pub fn into_temp(self) -> Temp {
+        self.temp
+    }
+
+    /// Give up ownership, see [`Temp::release`](struct.Temp.html#method.release).
+    pub fn release(self) -> PathBuf {
+        self.temp.release()
+    }
+}
+
+impl Default for TempPath {
+    fn default() -> Self {
+        TempPath::new()
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl TryFrom<Temp> for TempPath {
+    type Error = Temp;
+
+    /// Fails, giving the guard back, unless it was created as a path.
+    fn try_from(temp: Temp) -> Result<Self, Temp> {
+        match temp.kind {
+            Kind::Path => Ok(TempPath { temp }),
+            _ => Err(temp),
+        }
+    }
+}
+
+impl From<TempPath> for Temp {
+    fn from(path: TempPath) -> Self {
+        path.temp
+    }
+}
+
+impl Builder {
+    /// Create a temporary directory as a [`TempDir`](struct.TempDir.html).
+    #[track_caller]
+    pub fn temp_dir(&self) -> io::Result<TempDir> {
+        Ok(TempDir { temp: self.dir()? })
+    }
+
+    /// Generate a path as a [`TempPath`](struct.TempPath.html).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the default directory is rejected, like [`path`](#method.path).
+    #[track_caller]
+    pub fn temp_path(&self) -> TempPath {
+        TempPath { temp: self.path() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversion_checks_kind() {
+        let dir = TempDir::try_from(Temp::new_dir().unwrap()).unwrap();
+        assert!(dir.path().is_dir());
+        let temp: Temp = dir.into();
+
+        let temp = TempPath::try_from(temp).unwrap_err();
+        assert_eq!(temp.kind(), Kind::Dir);
+        let temp_file = TempDir::try_from(Temp::new_file().unwrap()).unwrap_err();
+        assert_eq!(temp_file.kind(), Kind::File);
+    }
+
+    #[test]
+    fn child_is_inside_dir() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.child("a"), "").unwrap();
+        let names: Vec<_> = dir
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["a"]);
+    }
+}
@@ -0,0 +1,230 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::error::Error;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io::{self, Read};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use Temp;
+
+/// A body passed to [`Temp::from_reader`](struct.Temp.html#method.from_reader) or
+/// [`Temp::from_async_read`](struct.Temp.html#method.from_async_read) was larger than the limit,
+/// e.g. to answer with `413 Payload Too Large`.
+///
+/// It is returned as the inner error of an `io::Error` of kind `InvalidData`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TooLargeError {
+    limit: u64,
+}
+
+impl TooLargeError {
+    /// The limit in bytes.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+}
+
+impl fmt::Display for TooLargeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the body is larger than {} bytes", self.limit)
+    }
+}
+
+impl Error for TooLargeError {}
+
+impl From<TooLargeError> for io::Error {
+    fn from(e: TooLargeError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+impl Temp {
+    /// Spool `reader` into a new temporary file, failing with a
+    /// [`TooLargeError`](struct.TooLargeError.html) once more than `limit` bytes have been read.
+    /// The file is removed on failure. Requires the `upload` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    ///
+    /// let body: &[u8] = b"--boundary\r\n...";
+    /// let upload = Temp::from_reader(body, 1024 * 1024).unwrap();
+    /// assert_eq!(fs::read(&upload).unwrap(), body);
+    ///
+    /// assert!(Temp::from_reader(body, 4).is_err());
+    /// ```
+    #[track_caller]
+    pub fn from_reader<R: Read>(reader: R, limit: u64) -> io::Result<Temp> {
+        let temp = Temp::new_file()?;
+        let mut file = OpenOptions::new().write(true).open(&temp)?;
+
+        // Read one byte past the limit to tell a body of exactly `limit` bytes from a larger one.
+        let written = io::copy(&mut reader.take(limit.saturating_add(1)), &mut file)?;
+        if written > limit {
+            return Err(TooLargeError { limit }.into());
+        }
+        Ok(temp)
+    }
+
+    /// Spool `reader` into a new temporary file without blocking, like
+    /// [`from_reader`](#method.from_reader), for request bodies in async handlers. The returned
+    /// future has to run within a tokio runtime. Requires the `upload` feature.
+    ///
+    /// Dropping the future before it completes, e.g. because the request was cancelled, removes
+    /// the file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate mktemp;
+    /// # extern crate tokio;
+    /// use mktemp::Temp;
+    /// use std::fs;
+    ///
+    /// # fn main() {
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    /// let body: &[u8] = b"uploaded";
+    /// let upload = runtime
+    ///     .block_on(Temp::from_async_read(body, 1024 * 1024))
+    ///     .unwrap();
+    /// assert_eq!(fs::read(&upload).unwrap(), body);
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn from_async_read<R: AsyncRead + Unpin>(reader: R, limit: u64) -> Spool<R> {
+        let file = Temp::new_file().and_then(|temp| {
+            let file = OpenOptions::new().write(true).open(&temp)?;
+            Ok((temp, File::from_std(file)))
+        });
+
+        Spool {
+            reader,
+            limit,
+            file: Some(file),
+            buf: vec![0; 64 * 1024].into_boxed_slice(),
+            start: 0,
+            end: 0,
+            read: 0,
+            eof: false,
+        }
+    }
+}
+
+/// The future returned by [`Temp::from_async_read`](struct.Temp.html#method.from_async_read).
+pub struct Spool<R> {
+    reader: R,
+    limit: u64,
+    file: Option<io::Result<(Temp, File)>>,
+    buf: Box<[u8]>,
+    // The part of `buf` that has been read but not written yet.
+    start: usize,
+    end: usize,
+    read: u64,
+    eof: bool,
+}
+
+impl<R> fmt::Debug for Spool<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Spool")
+            .field("limit", &self.limit)
+            .field("read", &self.read)
+            .finish()
+    }
+}
+
+impl<R: AsyncRead + Unpin> Future for Spool<R> {
+    type Output = io::Result<Temp>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<Temp>> {
+        let spool = self.get_mut();
+        let file = match spool.file {
+            Some(Ok((_, ref mut file))) => file,
+            Some(Err(_)) => return Poll::Ready(Err(spool.file.take().unwrap().unwrap_err())),
+            None => panic!("`Spool` polled after completion"),
+        };
+
+        loop {
+            if spool.start < spool.end {
+                let buf = &spool.buf[spool.start..spool.end];
+                match Pin::new(&mut *file).poll_write(cx, buf) {
+                    Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                    Poll::Ready(Ok(n)) => spool.start += n,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => return Poll::Pending,
+                }
+                continue;
+            }
+
+            if spool.eof {
+                return match Pin::new(&mut *file).poll_flush(cx) {
+                    Poll::Ready(Ok(())) => {
+                        let (temp, _file) = spool.file.take().unwrap().unwrap();
+                        Poll::Ready(Ok(temp))
+                    }
+                    Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+                    Poll::Pending => Poll::Pending,
+                };
+            }
+
+            let mut buf = ReadBuf::new(&mut spool.buf);
+            match Pin::new(&mut spool.reader).poll_read(cx, &mut buf) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+            let n = buf.filled().len();
+            spool.read += n as u64;
+            if spool.read > spool.limit {
+                return Poll::Ready(Err(TooLargeError { limit: spool.limit }.into()));
+            }
+            spool.eof = n == 0;
+            spool.start = 0;
+            spool.end = n;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn body_over_limit_is_rejected() {
+        let body = [7u8; 100];
+        assert_eq!(
+            fs::read(Temp::from_reader(&body[..], 100).unwrap())
+                .unwrap()
+                .len(),
+            100
+        );
+
+        let e = Temp::from_reader(&body[..], 99).unwrap_err();
+        let too_large = e.get_ref().unwrap().downcast_ref::<TooLargeError>();
+        assert_eq!(too_large.map(TooLargeError::limit), Some(99));
+    }
+
+    #[test]
+    fn async_body_is_spooled() {
+        let runtime = ::tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let body = vec![7u8; 200 * 1024];
+
+        let temp = runtime
+            .block_on(Temp::from_async_read(&body[..], body.len() as u64))
+            .unwrap();
+        assert_eq!(fs::read(&temp).unwrap(), body);
+
+        let result = runtime.block_on(Temp::from_async_read(&body[..], 1024));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
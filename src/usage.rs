@@ -0,0 +1,252 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::panic::Location;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use path_error::annotate;
+use {config, registry, Temp};
+
+static TRACKING: AtomicBool = AtomicBool::new(false);
+
+/// Turn on or off recording of the temporaries that are alive, for
+/// [`usage_report`](fn.usage_report.html).
+pub fn track_usage(track: bool) {
+    if TRACKING.swap(track, Ordering::Relaxed) != track {
+        if track {
+            registry::enable();
+        } else {
+            registry::disable();
+        }
+    }
+}
+
+/// The temporaries created at one place in the source that are still on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiteUsage {
+    /// Where the temporaries were created.
+    pub site: Option<&'static Location<'static>>,
+    /// How many of them exist.
+    pub count: usize,
+    /// The total size of their files in bytes, including the contents of directories.
+    pub bytes: u64,
+}
+
+/// Group the temporaries alive since [`track_usage`](fn.track_usage.html) was turned on by
+/// where they were created, largest first, to find the code responsible for filling the temp
+/// disk.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::Temp;
+/// use std::fs;
+///
+/// mktemp::track_usage(true);
+/// let temp_file = Temp::new_file().unwrap();
+/// fs::write(&temp_file, [0; 100]).unwrap();
+///
+/// let report = mktemp::usage_report();
+/// assert_eq!(report[0].count, 1);
+/// assert_eq!(report[0].bytes, 100);
+/// assert_eq!(report[0].site.unwrap().file(), file!());
+/// ```
+pub fn usage_report() -> Vec<SiteUsage> {
+    let mut by_site: HashMap<Option<(&'static str, u32, u32)>, SiteUsage> = HashMap::new();
+    for (path, record) in registry::snapshot() {
+        let site = record.site;
+        let bytes = match size(&path) {
+            Some(bytes) => bytes,
+            None => continue,
+        };
+
+        let key = site.map(|site| (site.file(), site.line(), site.column()));
+        let usage = by_site.entry(key).or_insert(SiteUsage {
+            site,
+            count: 0,
+            bytes: 0,
+        });
+        usage.count += 1;
+        usage.bytes += bytes;
+    }
+
+    let mut report: Vec<SiteUsage> = by_site.into_values().collect();
+    report.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(b.count.cmp(&a.count)));
+    report
+}
+
+/// The temporaries with one label that are still on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelUsage {
+    /// The label given with [`Builder::label`](struct.Builder.html#method.label), `None` for
+    /// temporaries without one.
+    pub label: Option<String>,
+    /// How many of them exist.
+    pub count: usize,
+    /// The total size of their files in bytes, including the contents of directories.
+    pub bytes: u64,
+}
+
+/// Group the temporaries alive since [`track_usage`](fn.track_usage.html) was turned on by
+/// their label, largest first, to see which purpose the temp disk is used for.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::Builder;
+/// use std::fs;
+///
+/// mktemp::track_usage(true);
+/// let staging = Builder::new().label("upload-staging").file().unwrap();
+/// fs::write(&staging, [0; 100]).unwrap();
+///
+/// let report = mktemp::usage_by_label();
+/// assert_eq!(report[0].label.as_ref().unwrap(), "upload-staging");
+/// assert_eq!(report[0].bytes, 100);
+/// ```
+pub fn usage_by_label() -> Vec<LabelUsage> {
+    let mut by_label: HashMap<Option<String>, LabelUsage> = HashMap::new();
+    for (path, record) in registry::snapshot() {
+        let bytes = match size(&path) {
+            Some(bytes) => bytes,
+            None => continue,
+        };
+
+        let label = record.label.map(|label| label.to_string());
+        let usage = by_label.entry(label.clone()).or_insert(LabelUsage {
+            label,
+            count: 0,
+            bytes: 0,
+        });
+        usage.count += 1;
+        usage.bytes += bytes;
+    }
+
+    let mut report: Vec<LabelUsage> = by_label.into_values().collect();
+    report.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(b.count.cmp(&a.count)));
+    report
+}
+
+/// The size of the file, or of everything in the directory, without following symlinks.
+pub(crate) fn size(path: &Path) -> Option<u64> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    if !metadata.is_dir() {
+        return Some(metadata.len());
+    }
+
+    let mut total = 0;
+    for entry in fs::read_dir(path).ok()?.flatten() {
+        total += size(&entry.path()).unwrap_or(0);
+    }
+    Some(total)
+}
+
+/// A path that dropping a temporary would remove, see
+/// [`Temp::cleanup_plan`](struct.Temp.html#method.cleanup_plan).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedRemoval {
+    /// The file, directory or symlink.
+    pub path: PathBuf,
+    /// The size of a file in bytes, zero for directories and symlinks.
+    pub bytes: u64,
+}
+
+impl Temp {
+    /// List what dropping the temporary would remove right now, in the order it would be
+    /// removed, so the contents of a directory come before the directory itself. Symlinks are
+    /// listed but not followed. Nothing is listed if the temporary doesn't exist or the
+    /// [`CleanupPolicy`](enum.CleanupPolicy.html) is `Keep`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    ///
+    /// let temp_dir = Temp::new_dir().unwrap();
+    /// fs::write(temp_dir.join("data"), [0; 100]).unwrap();
+    ///
+    /// let plan = temp_dir.cleanup_plan().unwrap();
+    /// let total: u64 = plan.iter().map(|removal| removal.bytes).sum();
+    /// println!("removing {} paths, {} bytes", plan.len(), total);
+    /// assert_eq!(plan.len(), 2);
+    /// assert_eq!(total, 100);
+    /// ```
+    pub fn cleanup_plan(&self) -> io::Result<Vec<PlannedRemoval>> {
+        let mut plan = Vec::new();
+        if self.fs.is_none() && config::keep() {
+            return Ok(plan);
+        }
+        match fs::symlink_metadata(&self.path) {
+            Ok(metadata) => plan_removal(&self.path, metadata, &mut plan)?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return annotate(Err(e), "read metadata of", &self.path),
+        }
+        Ok(plan)
+    }
+}
+
+fn plan_removal(
+    path: &Path,
+    metadata: fs::Metadata,
+    plan: &mut Vec<PlannedRemoval>,
+) -> io::Result<()> {
+    let bytes = if metadata.is_dir() {
+        for entry in annotate(fs::read_dir(path), "read directory", path)? {
+            let entry = annotate(entry, "read directory", path)?;
+            let child = entry.path();
+            let metadata = annotate(fs::symlink_metadata(&child), "read metadata of", &child)?;
+            plan_removal(&child, metadata, plan)?;
+        }
+        0
+    } else if metadata.is_file() {
+        metadata.len()
+    } else {
+        0
+    };
+    plan.push(PlannedRemoval {
+        path: path.to_path_buf(),
+        bytes,
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Temp;
+
+    #[test]
+    fn size_includes_dir_contents() {
+        let dir = Temp::new_dir().unwrap();
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("a"), [0; 10]).unwrap();
+        fs::write(dir.join("b"), [0; 5]).unwrap();
+
+        assert_eq!(size(&dir), Some(15));
+        assert_eq!(size(&dir.join("missing")), None);
+    }
+
+    #[test]
+    fn cleanup_plan_lists_contents_before_dirs() {
+        let dir = Temp::new_dir().unwrap();
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("a"), [0; 10]).unwrap();
+
+        let plan = dir.cleanup_plan().unwrap();
+        let expected = [
+            (dir.join("sub").join("a"), 10),
+            (dir.join("sub"), 0),
+            (dir.to_path_buf(), 0),
+        ];
+        let plan: Vec<(PathBuf, u64)> = plan.into_iter().map(|p| (p.path, p.bytes)).collect();
+        assert_eq!(plan, expected);
+
+        let missing = Temp::new_path();
+        assert_eq!(missing.cleanup_plan().unwrap(), []);
+    }
+}
@@ -0,0 +1,117 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::io;
+use std::marker::PhantomData;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use notify::{self, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use Temp;
+
+fn into_io(e: notify::Error) -> io::Error {
+    match e.kind {
+        notify::ErrorKind::Io(e) => e,
+        _ => io::Error::new(io::ErrorKind::Other, e),
+    }
+}
+
+/// Events for changes under a watched temporary directory, see
+/// [`Temp::watch`](struct.Temp.html#method.watch).
+///
+/// The watch borrows the temporary so it always stops before the directory is removed.
+pub struct Watch<'a> {
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    _temp: PhantomData<&'a Temp>,
+}
+
+impl<'a> Watch<'a> {
+    /// Block until the next event arrives.
+    pub fn recv(&self) -> Option<notify::Result<Event>> {
+        self.events.recv().ok()
+    }
+
+    /// Wait up to `timeout` for the next event.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<notify::Result<Event>> {
+        match self.events.recv_timeout(timeout) {
+            Ok(event) => Some(event),
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+
+    /// Return the next event if one is waiting, without blocking.
+    pub fn try_recv(&self) -> Option<notify::Result<Event>> {
+        self.events.try_recv().ok()
+    }
+}
+
+impl<'a> Iterator for Watch<'a> {
+    type Item = notify::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+impl Temp {
+    /// Watch for changes anywhere under this temporary directory, enabled with the `notify`
+    /// feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mktemp::Temp;
+    /// use std::fs;
+    /// use std::time::Duration;
+    ///
+    /// let temp_dir = Temp::new_dir().unwrap();
+    /// let watch = temp_dir.watch().unwrap();
+    ///
+    /// fs::write(temp_dir.join("file"), b"data").unwrap();
+    /// assert!(watch.recv_timeout(Duration::from_secs(10)).is_some());
+    /// ```
+    pub fn watch(&self) -> io::Result<Watch<'_>> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(into_io)?;
+        watcher
+            .watch(&self.path, RecursiveMode::Recursive)
+            .map_err(into_io)?;
+
+        Ok(Watch {
+            _watcher: watcher,
+            events,
+            _temp: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reports_nested_changes() {
+        let temp = Temp::new_dir().unwrap();
+        fs::create_dir(temp.join("sub")).unwrap();
+        let watch = temp.watch().unwrap();
+
+        let file = temp.join("sub").join("file");
+        fs::write(&file, b"data").unwrap();
+
+        let timeout = Duration::from_secs(10);
+        while let Some(event) = watch.recv_timeout(timeout) {
+            if event.unwrap().paths.contains(&file) {
+                return;
+            }
+        }
+        panic!("no event for {}", file.display());
+    }
+
+    #[test]
+    fn watching_missing_dir_fails() {
+        let temp = Temp::new_path();
+        assert!(temp.watch().is_err());
+    }
+}
@@ -0,0 +1,111 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use registry;
+use usage::size;
+
+/// Watches the total size of the temporaries alive in the process on a background thread and
+/// calls back when it grows past a threshold, so a long-running service can push back, e.g.
+/// stop accepting uploads or [trim](struct.TempCache.html#method.trim) a cache, before the
+/// file system fills up. The watchdog stops when it is dropped.
+///
+/// Only temporaries created while a watchdog, [`leak_check`](fn.leak_check.html) or
+/// [`track_usage`](fn.track_usage.html) is active are counted.
+///
+/// # Examples
+///
+/// ```
+/// use mktemp::{Temp, Watchdog};
+/// use std::fs;
+/// use std::sync::mpsc;
+/// use std::time::Duration;
+///
+/// let (sender, receiver) = mpsc::channel();
+/// let watchdog = Watchdog::start(1000, Duration::from_millis(10), move |total| {
+///     let _ = sender.send(total);
+/// });
+///
+/// let upload = Temp::new_file().unwrap();
+/// fs::write(&upload, vec![0; 2000]).unwrap();
+/// assert!(receiver.recv().unwrap() >= 2000);
+/// drop(watchdog);
+/// ```
+#[derive(Debug)]
+pub struct Watchdog {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Check the total size every `interval` and call `on_exceeded` with it each time it goes
+    /// from at most `threshold` bytes to more. It is called again only after the total has
+    /// dropped back to the threshold or below.
+    pub fn start<F>(threshold: u64, interval: Duration, on_exceeded: F) -> Watchdog
+    where
+        F: FnMut(u64) + Send + 'static,
+    {
+        registry::enable();
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stop = stop.clone();
+        let thread = thread::spawn(move || watch(threshold, interval, on_exceeded, &thread_stop));
+        Watchdog {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// The total size in bytes of the temporaries alive now, including the contents of
+    /// directories.
+    pub fn total(&self) -> u64 {
+        total()
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        let (ref stopped, ref wake) = *self.stop;
+        *stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        wake.notify_one();
+        if let Some(thread) = self.thread.take() {
+            // A panicking callback has already been reported by the thread.
+            let _result = thread.join();
+        }
+        registry::disable();
+    }
+}
+
+fn total() -> u64 {
+    registry::snapshot()
+        .iter()
+        .filter_map(|(path, _)| size(path))
+        .sum()
+}
+
+fn watch<F: FnMut(u64)>(
+    threshold: u64,
+    interval: Duration,
+    mut on_exceeded: F,
+    stop: &(Mutex<bool>, Condvar),
+) {
+    let (ref stopped, ref wake) = *stop;
+    let mut exceeded = false;
+    loop {
+        let total = total();
+        if total > threshold && !exceeded {
+            on_exceeded(total);
+        }
+        exceeded = total > threshold;
+
+        let guard = stopped.lock().unwrap_or_else(|e| e.into_inner());
+        let (guard, _timeout) = wake
+            .wait_timeout_while(guard, interval, |stopped| !*stopped)
+            .unwrap_or_else(|e| e.into_inner());
+        if *guard {
+            return;
+        }
+    }
+}
@@ -0,0 +1,20 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! The configuration can only be set once per process, so this has a test binary of its own.
+extern crate mktemp;
+
+use std::io;
+
+use mktemp::{config, configure, Config};
+
+#[test]
+fn configure_only_once() {
+    configure(Config::default()).unwrap();
+    assert_eq!(config(), Some(Config::default()));
+
+    match configure(Config::default()) {
+        Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+        _ => panic!(),
+    }
+}
@@ -0,0 +1,30 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! The creation limit applies to the whole process, so this has a test binary of its own.
+extern crate mktemp;
+
+use std::io;
+
+use mktemp::{CreationLimit, OnLimit, Temp, TempPath};
+
+#[test]
+fn paths_are_not_limited() {
+    mktemp::set_creation_limit(Some(CreationLimit {
+        max_live: Some(1),
+        on_limit: OnLimit::Error,
+        ..CreationLimit::default()
+    }));
+
+    let file = Temp::new_file().unwrap();
+    let path = Temp::new_path();
+    let typed = TempPath::new();
+    assert_eq!(
+        Temp::new_dir().unwrap_err().kind(),
+        io::ErrorKind::WouldBlock
+    );
+
+    drop(file);
+    let _dir = Temp::new_dir().unwrap();
+    drop((path, typed));
+}
@@ -0,0 +1,26 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+//! `set_symlink_free_root` applies to the whole process, so this has a test binary of its own.
+extern crate mktemp;
+
+use std::io;
+
+use mktemp::{Builder, Kind, Temp};
+
+#[test]
+fn rejected_root_fails_try_path() {
+    let dir = Temp::new_dir().unwrap();
+    let resolved = dir.canonicalize().unwrap();
+    mktemp::set_symlink_free_root(true);
+
+    mktemp::with_root(dir.join("missing"), || {
+        let e = Builder::new().try_path().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        let e = Builder::new().build(Kind::Path).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+    });
+    mktemp::with_root(&dir, || {
+        assert!(Builder::new().try_path().unwrap().starts_with(&resolved));
+    });
+}